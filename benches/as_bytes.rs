@@ -0,0 +1,44 @@
+//! Compares `Png::as_bytes`, which reuses the CRC cached in each chunk, against
+//! serializing the same file while computing every CRC again.
+//! `cargo test --benches` runs each benchmark once as a smoke test.
+
+use std::str::FromStr;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pngme::chunk::Chunk;
+use pngme::chunk_type::ChunkType;
+use pngme::png::Png;
+
+/// Builds a PNG with 64 chunks of 64 KiB each
+fn large_png() -> Png {
+    let chunk_type = ChunkType::from_str("ruSt").unwrap();
+    let chunks: Vec<Chunk> = (0..64)
+        .map(|i| Chunk::new(chunk_type.clone(), vec![i as u8; 64 * 1024]))
+        .collect();
+    Png::from_chunks(chunks)
+}
+
+/// Serializes `png` like `as_bytes` but without using the cached CRCs
+fn as_bytes_uncached(png: &Png) -> Vec<u8> {
+    let mut bytes: Vec<u8> = png.header().to_vec();
+    for chunk in png.chunks() {
+        bytes.extend_from_slice(&chunk.length().to_be_bytes());
+        bytes.extend_from_slice(&chunk.chunk_type().bytes());
+        bytes.extend_from_slice(chunk.data());
+        bytes.extend_from_slice(&chunk.compute_crc().to_be_bytes());
+    }
+    bytes
+}
+
+fn bench_as_bytes(c: &mut Criterion) {
+    let png = large_png();
+    let mut group = c.benchmark_group("as_bytes");
+    group.bench_function("cached_crc", |b| b.iter(|| black_box(&png).as_bytes()));
+    group.bench_function("uncached_crc", |b| {
+        b.iter(|| as_bytes_uncached(black_box(&png)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_as_bytes);
+criterion_main!(benches);
@@ -1,4 +1,5 @@
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -6,6 +7,36 @@ use std::path::PathBuf;
 pub struct Cli {
     #[command(subcommand)]
     pub command: PngMeArgs,
+    /// Only print errors and the data asked for, no status messages or reports
+    #[arg(long, short, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Also print the offset, size and CRC of each Chunk the command works on
+    #[arg(long, short, global = true)]
+    pub verbose: bool,
+}
+
+impl Cli {
+    /// How much the command should print, from `--quiet` and `--verbose`
+    pub fn verbosity(&self) -> Verbosity {
+        if self.quiet {
+            Verbosity::Quiet
+        } else if self.verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
+}
+
+/// How much a command prints besides errors and the data asked for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verbosity {
+    /// No status messages
+    Quiet,
+    /// Status messages such as what was added or removed
+    Normal,
+    /// Status messages and the details of each Chunk worked on
+    Verbose,
 }
 
 #[derive(Subcommand, Debug)]
@@ -20,37 +51,585 @@ pub enum PngMeArgs {
     Remove(RemoveArgs),
     /// Print Chunks from a file
     Print(PrintArgs),
+    /// Remove duplicated ancillary Chunks from a file
+    Dedup(DedupArgs),
+    /// Extract the EXIF metadata stored in an eXIf Chunk
+    Exif(ExifArgs),
+    /// Extract the raw data of a Chunk to a file
+    Extract(ExtractArgs),
+    /// Print a hash of the image header and data, ignoring any other Chunks
+    Fingerprint(FingerprintArgs),
+    /// Check that a file is a well formed PNG
+    Validate(ValidateArgs),
+    /// Rename every custom ancillary Chunk to start with a two letter prefix
+    Namespace(NamespaceArgs),
+    /// Estimate how likely it is that a file was modified by pngme
+    Detect(DetectArgs),
+    /// Print how many Chunks of each type a file has and their size
+    Count(CountArgs),
+    /// Report the bytes appended after the IEND Chunk
+    Trailer(TrailerArgs),
+    /// Print the image dimensions and how many Chunks of each type a file has
+    Info(InfoArgs),
+    /// Print one line per Chunk with its index, type, length, CRC and property flags
+    List(ListArgs),
+    /// Recompute the CRC of every Chunk and save the result
+    #[command(alias = "fix")]
+    Repair(RepairArgs),
+    /// Write each PNG file concatenated in a file to its own file
+    Split(SplitArgs),
+    /// Replace the message of a Chunk, keeping its position in the file
+    Replace(ReplaceArgs),
+    /// Obfuscate again with a new key every Chunk that decodes with the old one
+    Rekey(RekeyArgs),
+    /// Report the custom Chunks of a file, the bytes they hide and the limit per Chunk
+    Capacity(CapacityArgs),
+    /// List the Chunks added, removed or changed between two files
+    Diff(DiffArgs),
+    /// Print a completion script for a shell
+    Completions(CompletionsArgs),
 }
 
 #[derive(Args, Debug)]
 #[command(author, version, about)]
 pub struct EncodeArgs {
+    /// PNG file to read, or `-` to read it from stdin
     pub file: PathBuf,
     pub chunk_type: String,
-    pub message: String,
-    pub key: String,
+    /// The message to hide, `-` reads it from stdin
+    #[arg(required_unless_present_any = ["message_file", "message_env"])]
+    pub message: Option<String>,
+    /// Write the result to this file, required unless --in-place is given
+    #[arg(conflicts_with = "output_template")]
     pub output: Option<PathBuf>,
+    /// Write the result to a path derived from the input, e.g. `{dir}/out/{name}.{ext}`
+    #[arg(long, value_name = "TEMPLATE")]
+    pub output_template: Option<String>,
+    /// Overwrite the input file with the result
+    #[arg(long, conflicts_with_all = ["output", "output_template"])]
+    pub in_place: bool,
+    /// Hide the raw bytes of this file instead of a message
+    #[arg(long, value_name = "PATH", conflicts_with = "message")]
+    pub message_file: Option<PathBuf>,
+    /// Read the message from this environment variable, keeping it out of the command line
+    #[arg(long, value_name = "VARNAME", conflicts_with_all = ["message", "message_file"])]
+    pub message_env: Option<String>,
+    /// Obfuscate or encrypt the message with this key, see --cipher
+    #[arg(long)]
+    pub key: Option<String>,
+    /// Read the key from the first line of stdin, which then can't hold the PNG or the message
+    #[arg(long, conflicts_with = "key")]
+    pub key_stdin: bool,
+    /// Cipher used with the key, `xor` by default. Prompts for the key when there is
+    /// no --key. aes requires the `aes` feature
+    #[arg(long, value_enum)]
+    pub cipher: Option<CipherKind>,
+    /// Allow encoding an empty message
+    #[arg(long)]
+    pub allow_empty: bool,
+    /// Splice the raw chunk into the file at this byte offset, even mid-chunk
+    #[arg(long, value_name = "N")]
+    pub at_offset: Option<usize>,
+    /// Spread the message across the existing custom ancillary chunks, storing only
+    /// the layout in the new chunk
+    #[arg(long, conflicts_with = "at_offset")]
+    pub spread: bool,
+    /// Split the message into this many chunks whose sizes differ by at most one
+    /// byte, at most one per byte of the message
+    #[arg(long, value_name = "N", conflicts_with_all = ["at_offset", "spread"])]
+    pub parts: Option<NonZeroUsize>,
+    /// Compress the message with zlib before obfuscating and storing it
+    #[arg(long)]
+    pub compress: bool,
+    /// The message is base64, store the bytes it decodes to
+    #[arg(long)]
+    pub base64: bool,
+    /// Refuse to encode if the file would grow by more than this percentage
+    #[arg(long, value_name = "PERCENT")]
+    pub max_growth: Option<f64>,
+    /// Insert the chunk before acTL so it precedes every frame of an animated PNG
+    #[arg(long, conflicts_with = "at_offset")]
+    pub apng: bool,
+    /// Write a standard zTXt layout: the --keyword, a null byte, the compression method
+    /// and the zlib-compressed message encoded as Latin-1. Requires the `zTXt` Chunk type
+    #[arg(long, group = "text_chunk", requires = "keyword", conflicts_with_all = ["compress", "spread", "parts", "key", "key_stdin", "cipher"])]
+    pub ztxt: bool,
+    /// Write a standard tEXt layout: the --keyword, a null byte and the message encoded
+    /// as Latin-1. Requires the `tEXt` Chunk type
+    #[arg(long, group = "text_chunk", requires = "keyword", conflicts_with_all = ["compress", "spread", "parts", "key", "key_stdin", "cipher"])]
+    pub text: bool,
+    /// Keyword describing the text, such as `Comment`, 1 to 79 printable Latin-1
+    /// characters without leading, trailing or consecutive spaces
+    #[arg(long, requires = "text_chunk")]
+    pub keyword: Option<String>,
+    /// Insert the Chunk at this index among the Chunks instead of before IEND, from 1
+    /// right after IHDR up to the index of IEND
+    #[arg(long, value_name = "INDEX", conflicts_with_all = ["at_offset", "apng", "spread"])]
+    pub at: Option<usize>,
+    /// Fail if the file already has a Chunk of this type, instead of warning
+    #[arg(long, alias = "no-duplicates")]
+    pub no_duplicate: bool,
+    /// Append the message to the data of the first Chunk of this type when there is one,
+    /// instead of adding a new Chunk. The data is appended as is, so it can't be
+    /// obfuscated, encrypted, compressed or laid out as a text chunk.
+    #[arg(long, conflicts_with_all = ["at_offset", "spread", "parts", "at", "key", "key_stdin", "cipher", "compress", "text", "ztxt"])]
+    pub append_to_existing: bool,
+    /// Only report how many bytes the file would grow by, without writing it
+    #[arg(long)]
+    pub size_delta: bool,
+    /// Parse and modify the file in memory, then report what would change without writing it
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Append a line describing this operation to an audit log
+    #[arg(long)]
+    pub log: Option<PathBuf>,
 }
 
 #[derive(Args, Debug)]
 pub struct DecodeArgs {
+    /// PNG file to read, or `-` to read it from stdin
     pub file: PathBuf,
-    pub chunk_type: String,
-    pub key: String,
+    /// Parse the PNG embedded in the file at this byte offset, where its signature starts
+    #[arg(long, value_name = "N")]
+    pub offset: Option<usize>,
+    #[arg(required_unless_present = "try_all_with_key")]
+    pub chunk_type: Option<String>,
+    /// Key the message was obfuscated with when it was encoded
+    #[arg(long)]
+    pub key: Option<String>,
+    /// Read the key from the first line of stdin, which then can't hold the PNG
+    #[arg(long, conflicts_with = "key")]
+    pub key_stdin: bool,
+    /// Cipher the message was encoded with, `xor` by default. Prompts for the key
+    /// when there is no --key
+    #[arg(long, value_enum)]
+    pub cipher: Option<CipherKind>,
+    /// Write the raw message bytes to this file instead of printing them
+    #[arg(long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+    /// The chunk holds the layout of a message encoded with `encode --spread`
+    #[arg(long)]
+    pub spread: bool,
+    /// The message was compressed with `encode --compress`
+    #[arg(long)]
+    pub compress: bool,
+    /// The chunk has the zTXt layout, print its keyword and inflate its Latin-1 text.
+    /// Requires the `zTXt` Chunk type
+    #[arg(long, conflicts_with_all = ["compress", "spread", "all", "key", "key_stdin", "cipher", "encoding"])]
+    pub ztxt: bool,
+    /// The chunk has the tEXt layout, print its keyword and Latin-1 text. Requires the
+    /// `tEXt` Chunk type
+    #[arg(long, conflicts_with_all = ["ztxt", "compress", "spread", "all", "key", "key_stdin", "cipher", "encoding"])]
+    pub text: bool,
+    /// Print the message bytes as base64
+    #[arg(long, conflicts_with = "output")]
+    pub base64: bool,
+    /// Print the message of every chunk with this type, not just the first
+    #[arg(long, conflicts_with_all = ["output", "spread"])]
+    pub all: bool,
+    /// Only print the message if it contains this text, otherwise exit with an error
+    #[arg(long, value_name = "SUBSTRING", conflicts_with = "output")]
+    pub grep: Option<String>,
+    /// Text encoding of the message, anything but utf8 requires the `encoding` feature
+    #[arg(long, value_enum, default_value_t = TextEncoding::Utf8, conflicts_with_all = ["output", "base64"])]
+    pub encoding: TextEncoding,
+    /// Print the Chunk type, length and CRC with the message as a JSON object.
+    /// A message that isn't UTF-8 is given as base64
+    #[arg(long, conflicts_with_all = ["output", "base64", "all", "grep", "encoding", "ztxt", "text"])]
+    pub json: bool,
+    /// Decode every ancillary chunk with this key and print the readable ones
+    #[arg(long, value_name = "KEY", conflicts_with_all = ["chunk_type", "key"])]
+    pub try_all_with_key: Option<String>,
+    /// Match the Chunk type regardless of the case of its letters
+    #[arg(long)]
+    pub ignore_case: bool,
 }
 
 #[derive(Args, Debug)]
 pub struct FindArgs {
+    /// PNG file to read, or `-` to read it from stdin
     pub file: PathBuf,
+    /// Parse the PNG embedded in the file at this byte offset, where its signature starts
+    #[arg(long, value_name = "N")]
+    pub offset: Option<usize>,
+    /// Print at most this many chunks, the first ones found in the file
+    #[arg(long, value_name = "N")]
+    pub limit: Option<usize>,
+    /// Only print chunks whose data is valid UTF-8 text
+    #[arg(long)]
+    pub utf8_only: bool,
+    /// Only print chunks of this type
+    #[arg(long = "type", value_name = "TYPE")]
+    pub chunk_type: Option<String>,
+    /// Match the --type regardless of the case of its letters
+    #[arg(long, requires = "chunk_type")]
+    pub ignore_case: bool,
+    /// Print chunks in file order instead of the most likely to hold a message first
+    #[arg(long)]
+    pub stream: bool,
 }
 
 #[derive(Args, Debug)]
 pub struct RemoveArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+    /// Chunk types to remove, separated by commas or spaces
+    #[arg(required = true, value_delimiter = ',')]
+    pub chunk_types: Vec<String>,
+    /// Remove every chunk of each type instead of only the first one
+    #[arg(long)]
+    pub all: bool,
+    /// Allow removing critical chunks such as IHDR, IDAT or IEND
+    #[arg(long)]
+    pub force: bool,
+    /// Match the Chunk type regardless of the case of its letters
+    #[arg(long)]
+    pub ignore_case: bool,
+    /// Write the result to this file, required unless --in-place is given
+    #[arg(long, value_name = "PATH", conflicts_with = "output_template")]
+    pub output: Option<PathBuf>,
+    /// Write the result to a path derived from the input, e.g. `{dir}/out/{name}.{ext}`
+    #[arg(long, value_name = "TEMPLATE")]
+    pub output_template: Option<String>,
+    /// Overwrite the input file with the result
+    #[arg(long, conflicts_with_all = ["output", "output_template"])]
+    pub in_place: bool,
+    /// Only report how many bytes the file would shrink by, without writing it
+    #[arg(long)]
+    pub size_delta: bool,
+    /// Parse and modify the file in memory, then report what would change without writing it
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Append a line describing this operation to an audit log
+    #[arg(long)]
+    pub log: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ReplaceArgs {
+    /// PNG file to read, or `-` to read it from stdin
     pub file: PathBuf,
     pub chunk_type: String,
+    /// The new message
+    pub message: String,
+    /// Obfuscate the message with a XOR cipher using this key
+    #[arg(long)]
+    pub key: Option<String>,
+    /// Write the result to this file, required unless --in-place is given
+    #[arg(long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+    /// Overwrite the input file with the result
+    #[arg(long, conflicts_with = "output")]
+    pub in_place: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct RekeyArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+    /// Key the messages are obfuscated with
+    #[arg(long)]
+    pub old_key: String,
+    /// Key to obfuscate the messages with instead
+    #[arg(long)]
+    pub new_key: String,
+    /// Write the result to this file, required unless --in-place is given
+    #[arg(long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+    /// Overwrite the input file with the result
+    #[arg(long, conflicts_with = "output")]
+    pub in_place: bool,
 }
 
 #[derive(Args, Debug)]
 pub struct PrintArgs {
+    /// PNG file to read, or `-` to read it from stdin
     pub file: PathBuf,
+    /// Parse the PNG embedded in the file at this byte offset, where its signature starts
+    #[arg(long, value_name = "N", conflicts_with_all = ["since_offset", "multi"])]
+    pub offset: Option<usize>,
+    /// Resume printing from this byte offset, which must be a chunk boundary
+    #[arg(long, value_name = "OFFSET")]
+    pub since_offset: Option<u64>,
+    /// Print the Chunks as a JSON array
+    #[arg(long)]
+    pub json: bool,
+    /// Print one JSON object per Chunk and line, as JSON Lines
+    #[arg(long, conflicts_with = "json")]
+    pub jsonl: bool,
+    /// Only print the type of each Chunk, one per line
+    #[arg(long, conflicts_with_all = ["json", "jsonl"])]
+    pub types_only: bool,
+    /// Also print a CRC-32 of the whole file, to check later that it hasn't changed
+    #[arg(long, conflicts_with_all = ["json", "jsonl", "types_only"])]
+    pub checksum: bool,
+    /// Print every PNG file concatenated in the file, one after the other
+    #[arg(long, conflicts_with = "since_offset")]
+    pub multi: bool,
+    /// Accept any four bytes as a Chunk type instead of only letters
+    #[arg(long, conflicts_with = "multi")]
+    pub no_validate_type: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct DedupArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+    /// Keep the last occurrence of a duplicated chunk instead of the first
+    #[arg(long)]
+    pub keep_last: bool,
+    /// Write the result to this file, required unless --in-place is given
+    #[arg(long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+    /// Overwrite the input file with the result
+    #[arg(long, conflicts_with = "output")]
+    pub in_place: bool,
+    /// Append a line describing this operation to an audit log
+    #[arg(long)]
+    pub log: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ExifArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+    /// Parse the PNG embedded in the file at this byte offset, where its signature starts
+    #[arg(long, value_name = "N")]
+    pub offset: Option<usize>,
+    /// Write the raw EXIF bytes to this file instead of printing a summary
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ExtractArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+    /// Parse the PNG embedded in the file at this byte offset, where its signature starts
+    #[arg(long, value_name = "N")]
+    pub offset: Option<usize>,
+    pub chunk_type: String,
+    pub output: PathBuf,
+    /// Extract the chunk at this position among those of the type, counting from 0
+    #[arg(long, value_name = "N")]
+    pub index: Option<usize>,
+    /// Format used to write the chunk data
+    #[arg(long = "as", value_enum, default_value_t = DataFormat::Raw)]
+    pub format: DataFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataFormat {
+    /// The bytes as they are stored in the chunk
+    Raw,
+    /// Standard base64 with padding
+    Base64,
+    /// Lowercase hexadecimal
+    Hex,
+}
+
+#[derive(Args, Debug)]
+pub struct FingerprintArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+    /// Parse the PNG embedded in the file at this byte offset, where its signature starts
+    #[arg(long, value_name = "N")]
+    pub offset: Option<usize>,
+    /// Hash algorithm used for the fingerprint
+    #[arg(long, value_enum, default_value_t = HashAlgorithm::Sha256)]
+    pub hash: HashAlgorithm,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+    /// Requires the `sha1` feature
+    Sha1,
+    /// Requires the `blake3` feature
+    Blake3,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CipherKind {
+    /// Repeating-key XOR, only obfuscates the message
+    Xor,
+    /// AES-256-GCM with a key derived from the passphrase, requires the `aes` feature
+    Aes,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf8,
+    /// ISO-8859-1, decoded as its windows-1252 superset
+    Latin1,
+    Utf16le,
+    Utf16be,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Type,
+    Count,
+    Size,
+}
+
+#[derive(Args, Debug)]
+pub struct ValidateArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+    /// Validate the PNG embedded in the file at this byte offset, where its signature
+    /// starts. The data after its IEND belongs to the enclosing file and isn't checked.
+    #[arg(long, value_name = "N")]
+    pub offset: Option<usize>,
+    /// Require exactly one chunk of this type, can be repeated
+    #[arg(long, value_name = "TYPE")]
+    pub require_unique: Vec<String>,
+    /// Only check the 8 byte PNG signature without parsing any chunk
+    #[arg(long, conflicts_with = "require_unique")]
+    pub verify_signature_only: bool,
+    /// Also check that the chunks follow the ordering rules of the PNG spec
+    #[arg(long, conflicts_with = "verify_signature_only")]
+    pub strict_order: bool,
+    /// Stop at the first problem found, the default
+    #[arg(long)]
+    pub fail_fast: bool,
+    /// Report every problem found instead of stopping at the first one: wrong CRCs,
+    /// Chunks that aren't unique and the chunk order
+    #[arg(long, conflicts_with_all = ["fail_fast", "verify_signature_only"])]
+    pub collect_errors: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct NamespaceArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+    /// Two ASCII letters replacing the start of each custom chunk type
+    pub prefix: String,
+    /// Write the result to this file, required unless --in-place is given
+    #[arg(long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+    /// Overwrite the input file with the result
+    #[arg(long, conflicts_with = "output")]
+    pub in_place: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct DetectArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+    /// Parse the PNG embedded in the file at this byte offset, where its signature starts
+    #[arg(long, value_name = "N")]
+    pub offset: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub struct CountArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+    /// Parse the PNG embedded in the file at this byte offset, where its signature starts
+    #[arg(long, value_name = "N")]
+    pub offset: Option<usize>,
+    /// Print a final row with the total number of Chunks and bytes
+    #[arg(long)]
+    pub summary: bool,
+    /// Order the rows by this column instead of by first appearance
+    #[arg(long, value_enum)]
+    pub sort_by: Option<SortKey>,
+    /// Sort in descending order
+    #[arg(long, requires = "sort_by")]
+    pub desc: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct TrailerArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+    /// Read the PNG embedded in the file at this byte offset, where its signature
+    /// starts, and print the size of the data after its IEND
+    #[arg(long, value_name = "N", conflicts_with = "strip")]
+    pub offset: Option<usize>,
+    /// Remove the bytes after IEND and save the result
+    #[arg(long)]
+    pub strip: bool,
+    /// Write the stripped file to this file, required unless --in-place is given
+    #[arg(long, value_name = "PATH", requires = "strip")]
+    pub output: Option<PathBuf>,
+    /// Overwrite the input file with the stripped file
+    #[arg(long, requires = "strip", conflicts_with = "output")]
+    pub in_place: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct InfoArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+    /// Parse the PNG embedded in the file at this byte offset, where its signature starts
+    #[arg(long, value_name = "N")]
+    pub offset: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub struct RepairArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+    /// List every Chunk whose stored CRC was wrong before fixing it
+    #[arg(long)]
+    pub report: bool,
+    /// Write the result to this file, required unless --in-place is given
+    #[arg(long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+    /// Overwrite the input file with the result
+    #[arg(long, conflicts_with = "output")]
+    pub in_place: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SplitArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+    /// Path of each PNG file, `{index}` is replaced by its position starting at 0
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        default_value = "{dir}/{name}_{index}.{ext}"
+    )]
+    pub output_template: String,
+}
+
+#[derive(Args, Debug)]
+pub struct ListArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+    /// Parse the PNG embedded in the file at this byte offset, where its signature starts
+    #[arg(long, value_name = "N", conflicts_with = "no_validate_type")]
+    pub offset: Option<usize>,
+    /// Only list Chunks of this type
+    #[arg(long = "type", value_name = "TYPE")]
+    pub chunk_type: Option<String>,
+    /// Print the Chunks as a JSON array
+    #[arg(long)]
+    pub json: bool,
+    /// Accept any four bytes as a Chunk type instead of only letters
+    #[arg(long)]
+    pub no_validate_type: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CapacityArgs {
+    /// PNG file to read, or `-` to read it from stdin
+    pub file: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// PNG file before the changes, or `-` to read it from stdin
+    pub before: PathBuf,
+    /// PNG file after the changes, or `-` to read it from stdin
+    pub after: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct CompletionsArgs {
+    /// Shell to print the completion script for
+    #[arg(long, value_enum)]
+    pub shell: clap_complete::Shell,
 }
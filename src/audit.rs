@@ -0,0 +1,23 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::Result;
+
+/// Appends a line describing a mutating operation to the audit log at `log`.
+/// Each line holds the UNIX timestamp, the command, the file written and a detail
+/// (usually the chunk type) separated by tabs.
+pub fn record(log: &Path, command: &str, file: &Path, detail: &str) -> Result<()> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut log = OpenOptions::new().create(true).append(true).open(log)?;
+    writeln!(
+        log,
+        "{}\t{}\t{}\t{}",
+        timestamp,
+        command,
+        file.display(),
+        detail
+    )?;
+    Ok(())
+}
@@ -1,15 +1,24 @@
 use std::convert::TryFrom;
 use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
 
 use crate::chunk_type::ChunkType;
 use crate::{Error, Result};
 use crc::{Crc, CRC_32_ISO_HDLC};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+/// The CRC used by PNG chunks, computed over the chunk type and data
+pub(crate) const CRC_ALGORITHM: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
 
 #[derive(Debug, Clone)]
 pub struct Chunk {
     size: u32,
     chunk_type: ChunkType,
     data: Vec<u8>,
+    /// The data once it is shared between chunks with the same data by
+    /// `Png::share_duplicate_data`, `data` is left empty then
+    shared: Option<Arc<[u8]>>,
     crc: u32,
 }
 
@@ -19,23 +28,56 @@ impl Chunk {
     pub const CRC_BYTES: usize = 4;
     pub const MIN_BYTES: usize = Chunk::LENGTH_BYTES + Chunk::CHUNK_TYPE_BYTES + Chunk::CRC_BYTES;
 
-    /// Construct a new Chunk with a type and a data
+    /// Construct a new Chunk with a type and a data.
+    /// The length field is truncated if `data` holds more than `u32::MAX` bytes,
+    /// use `new_checked` when the size isn't known to be small.
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Self {
-        let checker: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-
-        let bytes: Vec<u8> = chunk_type
-            .bytes()
-            .iter()
-            .chain(data.iter())
-            .copied()
-            .collect();
-
         Self {
             size: data.len() as u32,
+            crc: Self::checksum(&chunk_type, &data),
             chunk_type,
             data,
-            crc: checker.checksum(&bytes),
+            shared: None,
+        }
+    }
+
+    /// Construct a new Chunk holding `message` from the text of its type, which is
+    /// validated like `ChunkType::from_str`
+    pub fn from_type_and_str(type_str: &str, message: &str) -> Result<Self> {
+        let chunk_type = ChunkType::from_str(type_str)?;
+        Self::new_checked(chunk_type, message.as_bytes().to_vec())
+    }
+
+    /// Computes the CRC of a chunk type and its data
+    fn checksum(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+        let mut digest = CRC_ALGORITHM.digest();
+        digest.update(&chunk_type.bytes());
+        digest.update(data);
+        digest.finalize()
+    }
+
+    /// Construct a new Chunk like `new`, but fails when `data` doesn't fit in the
+    /// 4 bytes length field.
+    pub fn new_checked(chunk_type: ChunkType, data: Vec<u8>) -> Result<Self> {
+        Self::check_length(data.len())?;
+        Ok(Self::new(chunk_type, data))
+    }
+
+    /// Fails when `len` bytes of data don't fit in the length field
+    fn check_length(len: usize) -> Result<()> {
+        if len > u32::MAX as usize {
+            return Err(ChunkError::DataTooLarge(len).into());
         }
+        Ok(())
+    }
+
+    /// Construct a new Chunk like `new`, but fails when `data` holds more than `max` bytes.
+    /// Useful when building chunks from untrusted input.
+    pub fn new_bounded(chunk_type: ChunkType, data: Vec<u8>, max: usize) -> Result<Self> {
+        if data.len() > max {
+            return Err(ChunkError::TooLarge(data.len(), max).into());
+        }
+        Ok(Self::new(chunk_type, data))
     }
 
     /// The length of the data in the Chunk
@@ -50,14 +92,61 @@ impl Chunk {
 
     /// The raw data of the Chunk in bytes
     pub fn data(&self) -> &[u8] {
-        &self.data
+        self.shared.as_deref().unwrap_or(&self.data)
     }
 
-    /// The CRC (Cyclic Redundancy Check) of this chunk
+    /// The CRC (Cyclic Redundancy Check) of this chunk, computed once when it was built,
+    /// or the one stored in the file for a chunk read with `from_bytes_unchecked`
     pub fn crc(&self) -> u32 {
         self.crc
     }
 
+    /// Computes the CRC of this chunk again from its type and data instead of
+    /// returning the cached value
+    pub fn compute_crc(&self) -> u32 {
+        Self::checksum(&self.chunk_type, self.data())
+    }
+
+    /// Returns true if the CRC of this chunk matches its type and data. Only a chunk
+    /// read with `from_bytes_unchecked` from a corrupted file can fail this.
+    pub fn verify_crc(&self) -> bool {
+        self.crc == self.compute_crc()
+    }
+
+    /// Checks that the length field matches the data and the cached CRC matches a
+    /// fresh computation, which could only break through a bug in a mutation API
+    pub fn is_consistent(&self) -> bool {
+        self.size as usize == self.data().len() && self.verify_crc()
+    }
+
+    /// Creates a `Chunk` from its type and data, the inverse of `into_parts`
+    pub fn from_parts((chunk_type, data): (ChunkType, Vec<u8>)) -> Self {
+        Self::new(chunk_type, data)
+    }
+
+    /// Consumes this chunk and returns its type and data
+    pub fn into_parts(self) -> (ChunkType, Vec<u8>) {
+        match self.shared {
+            Some(shared) => (self.chunk_type, shared.to_vec()),
+            None => (self.chunk_type, self.data),
+        }
+    }
+
+    /// Moves the data of this chunk to memory that other chunks can reference, see
+    /// `share_data`, and returns it
+    pub(crate) fn share(&mut self) -> Arc<[u8]> {
+        let data = std::mem::take(&mut self.data);
+        Arc::clone(self.shared.get_or_insert_with(|| data.into()))
+    }
+
+    /// Makes this chunk reference `data`, which must be equal to its own, instead of
+    /// holding a copy of it
+    pub(crate) fn share_data(&mut self, data: Arc<[u8]>) {
+        debug_assert_eq!(*data, *self.data());
+        self.data = Vec::new();
+        self.shared = Some(data);
+    }
+
     /// Returns the data stored in this chunk as a `String`.
     /// This function will return an error if the stored data is not valid UTF-8.
     pub fn data_as_string(&self) -> Result<String> {
@@ -70,14 +159,58 @@ impl Chunk {
     /// 3. The data itself *(`length` bytes)*
     /// 4. The CRC of the chunk type and data *(4 bytes)*
     pub fn as_bytes(&self) -> Vec<u8> {
-        self.length()
-            .to_be_bytes()
-            .iter()
-            .chain(self.chunk_type.bytes().iter())
-            .chain(self.data.iter())
-            .chain(self.crc.to_be_bytes().iter())
-            .copied()
-            .collect()
+        let mut bytes: Vec<u8> = Vec::with_capacity(Chunk::MIN_BYTES + self.data().len());
+        self.as_bytes_into(&mut bytes);
+        bytes
+    }
+
+    /// Appends the same byte sequence as `as_bytes` to the end of `buf`,
+    /// letting callers reuse one buffer across many chunks.
+    pub fn as_bytes_into(&self, buf: &mut Vec<u8>) {
+        buf.reserve(Chunk::MIN_BYTES + self.data().len());
+        buf.extend_from_slice(&self.length().to_be_bytes());
+        buf.extend_from_slice(&self.chunk_type.bytes());
+        buf.extend_from_slice(self.data());
+        buf.extend_from_slice(&self.crc.to_be_bytes());
+    }
+}
+
+impl Chunk {
+    /// Parses the structure of a chunk without checking its CRC. Returns the chunk,
+    /// whose CRC is computed from its type and data, along with the CRC stored in `bytes`.
+    /// The type is only checked to be made of letters when `validate_type` is set.
+    pub(crate) fn parse(bytes: &[u8], validate_type: bool) -> Result<(Self, u32)> {
+        let size: u32 = u32::from_be_bytes(Self::field(bytes, 0, "length")?);
+        let chunk_type = Self::field(bytes, Chunk::LENGTH_BYTES, "type")?;
+        let chunk_type: ChunkType = if validate_type {
+            ChunkType::try_from(chunk_type)?
+        } else {
+            ChunkType::from_bytes_unchecked(chunk_type)
+        };
+
+        let data_start = Chunk::LENGTH_BYTES + Chunk::CHUNK_TYPE_BYTES;
+        let data_end = data_start + size as usize;
+        let data: &[u8] = bytes
+            .get(data_start..data_end)
+            .ok_or(ChunkError::TruncatedData {
+                expected: size as usize,
+                actual: bytes.len().saturating_sub(data_start),
+            })?;
+        let input_crc: u32 = u32::from_be_bytes(Self::field(bytes, data_end, "CRC")?);
+
+        Ok((Chunk::new(chunk_type, data.to_vec()), input_crc))
+    }
+
+    /// Reads the 4 bytes field `name` starting at `start`
+    fn field(bytes: &[u8], start: usize, name: &'static str) -> Result<[u8; 4]> {
+        match bytes.get(start..start + 4) {
+            Some(field) => Ok(field.try_into().unwrap()),
+            None => Err(ChunkError::InvalidInput {
+                field: name,
+                actual: bytes.len().saturating_sub(start),
+            }
+            .into()),
+        }
     }
 }
 
@@ -85,42 +218,32 @@ impl TryFrom<&[u8]> for Chunk {
     type Error = Error;
 
     fn try_from(bytes: &[u8]) -> Result<Self> {
-        if bytes.len() < Chunk::MIN_BYTES {
-            return Err(Box::new(ChunkError::InvalidInput(bytes.len())));
-        }
-        let mut iter = bytes.iter().copied();
-
-        let size: [u8; Chunk::LENGTH_BYTES] = iter
-            .by_ref()
-            .take(Chunk::LENGTH_BYTES)
-            .collect::<Vec<u8>>()
-            .try_into()
-            .map_err(|_| fmt::Error)?;
-        let size: u32 = u32::from_be_bytes(size);
-
-        let chunk_type: [u8; Chunk::CHUNK_TYPE_BYTES] = iter
-            .by_ref()
-            .take(Chunk::CHUNK_TYPE_BYTES)
-            .collect::<Vec<u8>>()
-            .try_into()
-            .map_err(|_| fmt::Error)?;
-        let chunk_type: ChunkType = ChunkType::try_from(chunk_type)?;
-
-        let data: Vec<u8> = iter.by_ref().take(size as usize).collect();
-
-        let input_crc: [u8; Chunk::CRC_BYTES] = iter
-            .by_ref()
-            .take(Chunk::CRC_BYTES)
-            .collect::<Vec<u8>>()
-            .try_into()
-            .map_err(|_| fmt::Error)?;
-        let input_crc: u32 = u32::from_be_bytes(input_crc);
-
-        let chunk: Chunk = Chunk::new(chunk_type, data);
+        Chunk::parse(bytes, true).and_then(Chunk::check_crc)
+    }
+}
+
+impl Chunk {
+    /// Creates a `Chunk` from bytes like `try_from`, but accepts any four bytes as its
+    /// type, see `ChunkType::from_bytes_unchecked`. The CRC is still checked.
+    pub fn from_bytes_raw_type(bytes: &[u8]) -> Result<Self> {
+        Chunk::parse(bytes, false).and_then(Chunk::check_crc)
+    }
+
+    /// Creates a `Chunk` from bytes like `try_from`, but doesn't check the stored CRC:
+    /// the chunk keeps it as its `crc`, see `verify_crc`.
+    pub fn from_bytes_unchecked(bytes: &[u8]) -> Result<Self> {
+        let (chunk, stored) = Chunk::parse(bytes, true)?;
+        Ok(Chunk {
+            crc: stored,
+            ..chunk
+        })
+    }
+
+    /// Fails if the CRC stored in the file doesn't match the one of the parsed chunk
+    fn check_crc((chunk, input_crc): (Self, u32)) -> Result<Self> {
         if chunk.crc() != input_crc {
-            return Err(Box::new(ChunkError::InvalidCrc(chunk.crc(), input_crc)));
+            return Err(ChunkError::InvalidCrc(chunk.crc(), input_crc).into());
         }
-
         Ok(chunk)
     }
 }
@@ -136,19 +259,51 @@ impl fmt::Display for Chunk {
             self.data_as_string()
                 .unwrap_or_else(|_| "<Invalid UTF-8>".to_owned())
         )?;
-        writeln!(f, "\t\tCrc: {}", self.crc())?;
+        if self.verify_crc() {
+            writeln!(f, "\t\tCrc: {} (OK)", self.crc())?;
+        } else {
+            writeln!(
+                f,
+                "\t\tCrc: {} (MISMATCH expected {})",
+                self.crc(),
+                self.compute_crc()
+            )?;
+        }
         writeln!(f, "\t}}",)?;
         Ok(())
     }
 }
 
+/// Serializes the chunk metadata, the data itself is only described by its length
+impl Serialize for Chunk {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Chunk", 6)?;
+        state.serialize_field("type", self.chunk_type())?;
+        state.serialize_field("length", &self.length())?;
+        state.serialize_field("crc", &self.crc())?;
+        state.serialize_field("critical", &self.chunk_type().is_critical())?;
+        state.serialize_field("public", &self.chunk_type().is_public())?;
+        state.serialize_field("safe_to_copy", &self.chunk_type().is_safe_to_copy())?;
+        state.end()
+    }
+}
+
 #[derive(Debug)]
 pub enum ChunkError {
     /// The input crc do not match the real crc
     InvalidCrc(u32, u32),
 
-    /// The input is to small for the Chunk specifications
-    InvalidInput(usize),
+    /// The input ends before the 4 bytes of the named field, only `actual` are left
+    InvalidInput { field: &'static str, actual: usize },
+
+    /// The data is larger than the allowed maximum
+    TooLarge(usize, usize),
+
+    /// The data doesn't fit in the 4 bytes length field
+    DataTooLarge(usize),
+
+    /// The input ends before the number of data bytes declared by the length field
+    TruncatedData { expected: usize, actual: usize },
 }
 
 impl std::error::Error for ChunkError {}
@@ -161,11 +316,26 @@ impl fmt::Display for ChunkError {
                 "Invalid CRC for Chunk. Expected {} but found {}",
                 expected, actual
             ),
-            ChunkError::InvalidInput(actual) => write!(
+            ChunkError::InvalidInput { field, actual } => write!(
+                f,
+                "Not enough bytes for the Chunk {} field. Expected 4 but found {}",
+                field, actual
+            ),
+            ChunkError::TooLarge(actual, max) => write!(
+                f,
+                "Chunk data of {} bytes exceeds the maximum of {} bytes",
+                actual, max
+            ),
+            ChunkError::DataTooLarge(actual) => write!(
                 f,
-                "Invalid lenght of bytes. Expected {} but found {}",
-                Chunk::MIN_BYTES,
-                actual
+                "Chunk data of {} bytes doesn't fit in the length field, the maximum is {} bytes",
+                actual,
+                u32::MAX
+            ),
+            ChunkError::TruncatedData { expected, actual } => write!(
+                f,
+                "Chunk data is truncated. Expected {} bytes but found {}",
+                expected, actual
             ),
         }
     }
@@ -175,6 +345,7 @@ impl fmt::Display for ChunkError {
 mod tests {
     use super::*;
     use crate::chunk_type::ChunkType;
+    use crate::PngMeError;
     use std::str::FromStr;
 
     fn testing_chunk() -> Chunk {
@@ -259,6 +430,17 @@ mod tests {
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_crc_matches_checksum_of_type_and_data() {
+        let data: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+        let chunk = Chunk::new(ChunkType::from_str("RuSt").unwrap(), data.clone());
+
+        let bytes: Vec<u8> = b"RuSt".iter().chain(data.iter()).copied().collect();
+        assert_eq!(chunk.crc(), CRC_ALGORITHM.checksum(&bytes));
+        assert_eq!(chunk.compute_crc(), chunk.crc());
+        assert_eq!(testing_chunk().crc(), 2882656334);
+    }
+
     #[test]
     fn test_invalid_chunk_from_bytes() {
         let data_length: u32 = 42;
@@ -280,6 +462,164 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_truncated_chunk_data() {
+        let chunk_data: Vec<u8> = testing_chunk().as_bytes();
+        // Drops the CRC and the last 6 bytes of data, leaving 36 of the 42 data bytes
+        let truncated = &chunk_data[..chunk_data.len() - 10];
+
+        let err = Chunk::try_from(truncated).unwrap_err();
+        assert!(matches!(
+            err,
+            PngMeError::Chunk(ChunkError::TruncatedData {
+                expected: 42,
+                actual: 36
+            })
+        ));
+    }
+
+    #[test]
+    fn test_missing_field_names_the_field() {
+        let chunk_data: Vec<u8> = testing_chunk().as_bytes();
+        let crc_start = chunk_data.len() - Chunk::CRC_BYTES;
+
+        for (len, field) in [(2, "length"), (6, "type"), (crc_start + 1, "CRC")] {
+            let err = Chunk::try_from(&chunk_data[..len]).unwrap_err();
+            assert!(
+                matches!(err, PngMeError::Chunk(ChunkError::InvalidInput { field: f, .. }) if f == field)
+            );
+            assert!(err.to_string().contains(&format!("Chunk {} field", field)));
+        }
+    }
+
+    #[test]
+    fn test_from_bytes_raw_type() {
+        let chunk_type = ChunkType::from_bytes_unchecked(*b"Ru5t");
+        let bytes = Chunk::new(chunk_type, b"data".to_vec()).as_bytes();
+
+        assert!(Chunk::try_from(bytes.as_slice()).is_err());
+        let chunk = Chunk::from_bytes_raw_type(&bytes).unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "Ru5t");
+        assert_eq!(chunk.data(), b"data");
+    }
+
+    #[test]
+    fn test_from_bytes_unchecked() {
+        let mut bytes = testing_chunk().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        assert!(Chunk::try_from(bytes.as_slice()).is_err());
+        let chunk = Chunk::from_bytes_unchecked(&bytes).unwrap();
+        assert_eq!(chunk.compute_crc(), testing_chunk().crc());
+        assert_eq!(chunk.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_into_and_from_parts() {
+        let (chunk_type, data) = testing_chunk().into_parts();
+        assert_eq!(chunk_type.to_string(), "RuSt");
+        assert_eq!(data, b"This is where your secret message will be!");
+
+        let chunk = Chunk::from_parts(testing_chunk().into_parts());
+        assert_eq!(chunk.as_bytes(), testing_chunk().as_bytes());
+
+        // The data is moved in and out, not copied
+        let data = b"moved".to_vec();
+        let pointer = data.as_ptr();
+        let chunk = Chunk::new(ChunkType::from_str("RuSt").unwrap(), data);
+        assert_eq!(chunk.data().as_ptr(), pointer);
+        assert_eq!(chunk.into_parts().1.as_ptr(), pointer);
+    }
+
+    #[test]
+    fn test_verify_crc() {
+        let bytes = testing_chunk().as_bytes();
+        let chunk = Chunk::from_bytes_unchecked(&bytes).unwrap();
+        assert!(chunk.verify_crc());
+        assert!(chunk.to_string().contains("Crc: 2882656334 (OK)"));
+
+        let mut bytes = bytes;
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        let chunk = Chunk::from_bytes_unchecked(&bytes).unwrap();
+        assert!(!chunk.verify_crc());
+        assert_ne!(chunk.crc(), chunk.compute_crc());
+        assert!(chunk
+            .to_string()
+            .contains(&format!("(MISMATCH expected {})", chunk.compute_crc())));
+    }
+
+    #[test]
+    fn test_from_type_and_str() {
+        let chunk =
+            Chunk::from_type_and_str("RuSt", "This is where your secret message will be!").unwrap();
+        assert_eq!(chunk.as_bytes(), testing_chunk().as_bytes());
+
+        assert!(Chunk::from_type_and_str("Ru1t", "message").is_err());
+    }
+
+    #[test]
+    fn test_is_consistent() {
+        let chunk = testing_chunk();
+        assert!(chunk.is_consistent());
+
+        let mut desynced = testing_chunk();
+        desynced.size += 1;
+        assert!(!desynced.is_consistent());
+
+        let mut desynced = testing_chunk();
+        desynced.data.push(b'!');
+        desynced.size += 1;
+        assert!(!desynced.is_consistent());
+    }
+
+    #[test]
+    fn test_new_checked() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let chunk = Chunk::new_checked(chunk_type, b"small".to_vec()).unwrap();
+        assert_eq!(chunk.length(), 5);
+
+        assert!(Chunk::check_length(u32::MAX as usize).is_ok());
+        let err = Chunk::check_length(u32::MAX as usize + 1).unwrap_err();
+        assert!(matches!(
+            err,
+            PngMeError::Chunk(ChunkError::DataTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn test_new_bounded() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let data = vec![0; 16];
+
+        assert!(Chunk::new_bounded(chunk_type.clone(), data.clone(), 17).is_ok());
+        assert!(Chunk::new_bounded(chunk_type.clone(), data.clone(), 16).is_ok());
+        assert!(Chunk::new_bounded(chunk_type, data, 15).is_err());
+    }
+
+    #[test]
+    fn test_as_bytes_into() {
+        let chunk = testing_chunk();
+        let mut buf: Vec<u8> = vec![1, 2, 3];
+        chunk.as_bytes_into(&mut buf);
+
+        assert_eq!(&buf[..3], &[1, 2, 3]);
+        assert_eq!(&buf[3..], chunk.as_bytes().as_slice());
+    }
+
+    #[test]
+    fn test_serialize() {
+        let chunk = testing_chunk();
+        let json = serde_json::to_value(&chunk).unwrap();
+        assert_eq!(json["type"], "RuSt");
+        assert_eq!(json["length"], 42);
+        assert_eq!(json["crc"], 2882656334u32);
+        assert_eq!(json["critical"], true);
+        assert_eq!(json["public"], false);
+        assert_eq!(json["safe_to_copy"], true);
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
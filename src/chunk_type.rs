@@ -2,6 +2,8 @@ use std::convert::TryFrom;
 use std::fmt;
 use std::str::FromStr;
 
+use serde::{Serialize, Serializer};
+
 use crate::{Error, Result};
 
 /// A validated PNG chunk type. See the PNG spec for more details.
@@ -13,6 +15,12 @@ pub struct ChunkType {
 
 #[allow(dead_code)]
 impl ChunkType {
+    /// Creates a `ChunkType` from any four bytes, skipping the A-Z/a-z check.
+    /// Meant for inspecting files that don't follow the PNG spec.
+    pub fn from_bytes_unchecked(bytes: [u8; 4]) -> Self {
+        Self { identifier: bytes }
+    }
+
     /// Returns the raw bytes contained in the ChunkType
     pub fn bytes(&self) -> [u8; 4] {
         self.identifier
@@ -52,7 +60,14 @@ impl ChunkType {
         valid_bytes && self.is_reserved_bit_valid()
     }
 
+    /// Returns true if both types have the same letters, ignoring their case. The
+    /// case of each letter carries a property bit, not the identity of the type.
+    pub fn eq_ignore_ascii_case(&self, other: &ChunkType) -> bool {
+        self.identifier.eq_ignore_ascii_case(&other.identifier)
+    }
+
     /// Valid bytes are represented by the characters A-Z or a-z
+    #[allow(clippy::manual_range_contains)]
     pub fn is_valid_byte(byte: u8) -> bool {
         (byte >= b'a' && byte <= b'z') || (byte >= b'A' && byte <= b'Z')
     }
@@ -67,7 +82,7 @@ impl TryFrom<[u8; 4]> for ChunkType {
         if valid_bytes {
             Ok(Self { identifier: bytes })
         } else {
-            Err(Box::new(ChunkTypeError::InvalidCharacter))
+            Err(ChunkTypeError::InvalidCharacter.into())
         }
     }
 }
@@ -82,15 +97,21 @@ impl FromStr for ChunkType {
             let bytes: [u8; 4] = bytes.try_into().unwrap();
             Ok(Self::try_from(bytes)?)
         } else {
-            Err(Box::new(ChunkTypeError::ByteLengthError(bytes.len())))
+            Err(ChunkTypeError::ByteLengthError(bytes.len()).into())
         }
     }
 }
 
 impl fmt::Display for ChunkType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let s = String::from_utf8(self.identifier.to_vec()).map_err(|_| fmt::Error)?;
-        write!(f, "{}", s)
+        write!(f, "{}", String::from_utf8_lossy(&self.identifier))
+    }
+}
+
+/// Serializes as the four letter string, e.g. `"IHDR"`
+impl Serialize for ChunkType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
     }
 }
 
@@ -204,6 +225,14 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_chunk_type_eq_ignore_ascii_case() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        assert!(chunk_type.eq_ignore_ascii_case(&ChunkType::from_str("ruSt").unwrap()));
+        assert!(chunk_type.eq_ignore_ascii_case(&ChunkType::from_bytes_unchecked(*b"rust")));
+        assert!(!chunk_type.eq_ignore_ascii_case(&ChunkType::from_str("RuSu").unwrap()));
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
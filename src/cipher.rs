@@ -1,5 +1,11 @@
+use std::fmt;
+
+#[cfg(feature = "aes")]
+use crate::Result;
+
 /// Encode a message given in bytes using the key
 /// and return the encoded bytes
+#[allow(clippy::len_zero)]
 pub fn xor_encode(data: &[u8], key: &str) -> Vec<u8> {
     if key.len() == 0 {
         return data.to_vec();
@@ -14,6 +20,7 @@ pub fn xor_encode(data: &[u8], key: &str) -> Vec<u8> {
 
 /// Decode a message given in bytes using the key
 /// and return the message as a String
+#[allow(clippy::len_zero)]
 pub fn xor_decode(data: &[u8], key: &str) -> String {
     if key.len() == 0 {
         return String::from_utf8(data.to_vec()).unwrap();
@@ -28,3 +35,117 @@ pub fn xor_decode(data: &[u8], key: &str) -> String {
 
     String::from_utf8(result).unwrap()
 }
+
+/// Length of the random salt the AES key is derived with
+#[cfg(feature = "aes")]
+const AES_SALT_BYTES: usize = 16;
+
+/// Length of the AES-GCM nonce
+#[cfg(feature = "aes")]
+const AES_NONCE_BYTES: usize = 12;
+
+/// PBKDF2 rounds used to derive the AES key from the passphrase
+#[cfg(feature = "aes")]
+const AES_KDF_ROUNDS: u32 = 100_000;
+
+/// Derives a 256 bits key from `passphrase` and `salt` with PBKDF2-HMAC-SHA256
+#[cfg(feature = "aes")]
+fn derive_key(passphrase: &str, salt: &[u8]) -> aes_gcm::Key<aes_gcm::Aes256Gcm> {
+    let mut key = aes_gcm::Key::<aes_gcm::Aes256Gcm>::default();
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(passphrase.as_bytes(), salt, AES_KDF_ROUNDS, &mut key);
+    key
+}
+
+/// Encrypts `data` with AES-256-GCM using a key derived from `passphrase`.
+/// The random salt and nonce are stored in front of the ciphertext.
+#[cfg(feature = "aes")]
+pub fn aes_encrypt(data: &[u8], passphrase: &str) -> Vec<u8> {
+    use aes_gcm::aead::rand_core::RngCore;
+    use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+
+    let mut salt = [0; AES_SALT_BYTES];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = aes_gcm::Aes256Gcm::generate_nonce(&mut OsRng);
+    let cipher = aes_gcm::Aes256Gcm::new(&derive_key(passphrase, &salt));
+    let ciphertext = cipher
+        .encrypt(&nonce, data)
+        .expect("AES-GCM encryption can't fail for messages that fit in memory");
+
+    [&salt[..], &nonce[..], &ciphertext[..]].concat()
+}
+
+/// Decrypts data written by `aes_encrypt`, failing if `passphrase` is wrong or the
+/// data was tampered with
+#[cfg(feature = "aes")]
+pub fn aes_decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    use aes_gcm::aead::{Aead, KeyInit};
+
+    if data.len() < AES_SALT_BYTES + AES_NONCE_BYTES {
+        return Err(CipherError::TooShort(data.len()).into());
+    }
+    let (salt, rest) = data.split_at(AES_SALT_BYTES);
+    let (nonce, ciphertext) = rest.split_at(AES_NONCE_BYTES);
+    let cipher = aes_gcm::Aes256Gcm::new(&derive_key(passphrase, salt));
+    match cipher.decrypt(aes_gcm::Nonce::from_slice(nonce), ciphertext) {
+        Ok(message) => Ok(message),
+        Err(_) => Err(CipherError::Authentication.into()),
+    }
+}
+
+#[derive(Debug)]
+pub enum CipherError {
+    TooShort(usize),
+    Authentication,
+}
+
+impl std::error::Error for CipherError {}
+
+impl fmt::Display for CipherError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CipherError::TooShort(actual) => write!(
+                f,
+                "Encrypted data of {} bytes is too short to hold a salt and a nonce",
+                actual
+            ),
+            CipherError::Authentication => write!(
+                f,
+                "The message can't be decrypted, the key is wrong or the data was tampered with"
+            ),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "aes"))]
+mod tests {
+    use super::*;
+    use crate::PngMeError;
+
+    #[test]
+    fn test_aes_round_trip() {
+        let encrypted = aes_encrypt(b"secret message", "passphrase");
+        assert_ne!(
+            &encrypted[AES_SALT_BYTES + AES_NONCE_BYTES..],
+            b"secret message"
+        );
+        assert_eq!(
+            aes_decrypt(&encrypted, "passphrase").unwrap(),
+            b"secret message"
+        );
+    }
+
+    #[test]
+    fn test_aes_detects_tampering() {
+        let mut encrypted = aes_encrypt(b"secret message", "passphrase");
+        assert!(aes_decrypt(&encrypted, "wrong passphrase").is_err());
+
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 1;
+        let err = aes_decrypt(&encrypted, "passphrase").unwrap_err();
+        assert!(matches!(
+            err,
+            PngMeError::Cipher(CipherError::Authentication)
+        ));
+        assert!(aes_decrypt(&encrypted[..10], "passphrase").is_err());
+    }
+}
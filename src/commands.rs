@@ -1,70 +1,4939 @@
+use std::fmt;
 use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use crate::args::{DecodeArgs, EncodeArgs, FindArgs, PrintArgs, RemoveArgs};
-use crate::chunk::Chunk;
-use crate::chunk_type::ChunkType;
-use crate::cipher;
-use crate::png::Png;
+use base64::Engine;
+use clap::CommandFactory;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::args::{
+    CapacityArgs, CipherKind, Cli, CompletionsArgs, CountArgs, DataFormat, DecodeArgs, DedupArgs,
+    DetectArgs, DiffArgs, EncodeArgs, ExifArgs, ExtractArgs, FindArgs, FingerprintArgs,
+    HashAlgorithm, InfoArgs, ListArgs, NamespaceArgs, PrintArgs, RekeyArgs, RemoveArgs, RepairArgs,
+    ReplaceArgs, SortKey, SplitArgs, TextEncoding, TrailerArgs, ValidateArgs, Verbosity,
+};
+use crate::audit;
+use pngme::chunk::Chunk;
+use pngme::chunk_type::ChunkType;
+use pngme::cipher;
+use pngme::png::{Capacity, CrcMismatch, ImageHeader, Png, PngError, PossibleMessage};
 
 use crate::Result;
 
-/// Encodes a message into a PNG file and saves the result
-pub fn encode(args: EncodeArgs) -> Result<()> {
-    let chunk_type = ChunkType::from_str(&args.chunk_type)?;
-    let data = cipher::xor_encode(args.message.as_bytes(), &args.key);
-    let new_chunk = Chunk::new(chunk_type, data);
+#[derive(Debug)]
+pub enum CommandError {
+    /// The message to encode is empty
+    EmptyMessage,
 
-    let mut png: Png = Png::from_file(&args.file)?;
-    png.append_chunk(new_chunk);
+    /// The byte offset is past the end of a file of the given size
+    OffsetOutOfRange(usize, usize),
 
-    match args.output {
-        Some(file) => fs::write(file, png.as_bytes())?,
-        None => fs::write(args.file, png.as_bytes())?,
+    /// The feature needed by the requested operation was not compiled in
+    #[cfg_attr(
+        all(
+            feature = "sha1",
+            feature = "blake3",
+            feature = "encoding",
+            feature = "aes"
+        ),
+        allow(dead_code)
+    )]
+    MissingFeature(&'static str),
+
+    /// A chunk type required to be unique appears the given number of times
+    NotUnique(String, usize),
+
+    /// There are no custom ancillary chunks to spread a message across
+    NoCarrierChunks,
+
+    /// The layout of a spread message doesn't match the file
+    InvalidLayout,
+
+    /// Removing this critical chunk would corrupt the file
+    CriticalChunk(String),
+
+    /// The message isn't a valid zlib stream
+    NotCompressed,
+
+    /// The decoded message doesn't contain the searched text
+    NoMatch(String),
+
+    /// The message to encode isn't valid base64
+    InvalidBase64(String),
+
+    /// The key read from stdin is empty
+    EmptyKey,
+
+    /// The key to rekey from is empty, which would match every plain text chunk
+    EmptyOldKey,
+
+    /// Validation found the given problems
+    ValidationFailed(Vec<String>),
+
+    /// Encoding grows the file by more than the allowed percentage
+    GrowthExceeded(f64, f64),
+
+    /// The message isn't valid text in the given encoding
+    InvalidText(&'static str),
+
+    /// A chunk of this type already exists and duplicates were refused
+    DuplicateChunk(String),
+
+    /// The keyword of a text chunk isn't 1 to 79 printable Latin-1 characters, or has
+    /// leading, trailing or consecutive spaces
+    InvalidKeyword(String),
+
+    /// The data doesn't follow the layout of a text chunk, for the given reason
+    InvalidTextChunk(&'static str),
+
+    /// The layout of a text chunk was asked for with a chunk of another type than the
+    /// one it belongs to
+    TextLayoutMismatch(&'static str, String),
+
+    /// The message has fewer bytes than the number of parts asked for
+    TooManyParts(usize, usize),
+
+    /// The environment variable holding the message isn't set or isn't valid Unicode
+    MissingEnvVar(String),
+
+    /// The PNG was read from stdin and there is no `--output` to write the result to
+    StdinWithoutOutput,
+
+    /// `--key-stdin` was given while stdin already holds the PNG or the message
+    StdinTaken,
+
+    /// Both PNGs to compare were to be read from stdin
+    StdinTwice,
+
+    /// There is no output and overwriting the input file wasn't asked for
+    InPlaceRequired(PathBuf),
+}
+
+impl std::error::Error for CommandError {}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::EmptyMessage => {
+                write!(f, "Refusing to encode an empty message, use --allow-empty")
+            }
+            CommandError::OffsetOutOfRange(offset, size) => write!(
+                f,
+                "Offset {} is out of range for a file of {} bytes",
+                offset, size
+            ),
+            CommandError::MissingFeature(feature) => {
+                write!(f, "pngme was built without the '{}' feature", feature)
+            }
+            CommandError::NotUnique(chunk_type, count) => write!(
+                f,
+                "Expected exactly one Chunk '{}' but found {}",
+                chunk_type, count
+            ),
+            CommandError::NoCarrierChunks => {
+                write!(
+                    f,
+                    "The file has no custom ancillary Chunks to spread the message"
+                )
+            }
+            CommandError::InvalidLayout => {
+                write!(f, "The layout of the spread message doesn't match the file")
+            }
+            CommandError::InvalidBase64(reason) => {
+                write!(f, "The message isn't valid base64: {}", reason)
+            }
+            CommandError::NoMatch(pattern) => {
+                write!(f, "The message doesn't contain '{}'", pattern)
+            }
+            CommandError::NotCompressed => write!(
+                f,
+                "The message isn't compressed, or the key is wrong. Try without --compress"
+            ),
+            CommandError::EmptyKey => write!(f, "The key read from stdin is empty"),
+            CommandError::EmptyOldKey => write!(
+                f,
+                "The --old-key is empty, every plain text Chunk would be rekeyed"
+            ),
+            CommandError::ValidationFailed(problems) => {
+                write!(f, "Found {} problem(s) in the file:", problems.len())?;
+                for problem in problems.iter() {
+                    write!(f, "\n{}", problem)?;
+                }
+                Ok(())
+            }
+            CommandError::GrowthExceeded(growth, max) => write!(
+                f,
+                "Encoding grows the file by {:.1}%, more than the allowed {}%",
+                growth, max
+            ),
+            CommandError::InvalidText(encoding) => {
+                write!(f, "The message isn't valid {} text", encoding)
+            }
+            CommandError::DuplicateChunk(chunk_type) => write!(
+                f,
+                "The file already has a Chunk '{}', use replace to update it or --append-to-existing to add to it",
+                chunk_type
+            ),
+            CommandError::InvalidKeyword(keyword) => write!(
+                f,
+                "Invalid keyword '{}', it must be 1 to 79 printable Latin-1 characters without leading, trailing or consecutive spaces",
+                keyword
+            ),
+            CommandError::InvalidTextChunk(reason) => {
+                write!(f, "The Chunk isn't a valid text Chunk: {}", reason)
+            }
+            CommandError::TextLayoutMismatch(expected, chunk_type) => write!(
+                f,
+                "The {} layout belongs to '{}' Chunks, not to '{}'",
+                expected, expected, chunk_type
+            ),
+            CommandError::TooManyParts(parts, len) => write!(
+                f,
+                "Can't split a message of {} byte(s) into {} parts, some would be empty",
+                len, parts
+            ),
+            CommandError::MissingEnvVar(name) => write!(
+                f,
+                "The environment variable '{}' isn't set or isn't valid Unicode",
+                name
+            ),
+            CommandError::StdinWithoutOutput => write!(
+                f,
+                "The PNG was read from stdin, use --output to choose where to write the result"
+            ),
+            CommandError::StdinTaken => write!(
+                f,
+                "--key-stdin can't be used when the PNG or the message is read from stdin too"
+            ),
+            CommandError::StdinTwice => write!(f, "Only one of the PNGs can be read from stdin"),
+            CommandError::InPlaceRequired(file) => write!(
+                f,
+                "Refusing to overwrite '{}', use --in-place to modify it or --output to write the result elsewhere",
+                file.display()
+            ),
+            CommandError::CriticalChunk(chunk_type) => write!(
+                f,
+                "Chunk '{}' is critical, removing it corrupts the PNG. Use --force to remove it anyway",
+                chunk_type
+            ),
+        }
     }
-    println!("Chunk '{}' added", args.chunk_type);
-    Ok(())
 }
 
-/// Searches for a message hidden in a PNG file and prints the message if one is found
-pub fn decode(args: DecodeArgs) -> Result<()> {
-    let png: Png = Png::from_file(&args.file)?;
-    let find_chunk = png.chunk_by_type(&args.chunk_type);
-    match find_chunk {
-        Some(chunk) => println!("Message: {}", cipher::xor_decode(chunk.data(), &args.key)),
-        None => println!("No message for Chunk '{}'", args.chunk_type),
+/// What `encode` did
+#[derive(Debug, PartialEq, Eq)]
+pub struct EncodeOutcome {
+    pub chunk_type: String,
+    /// Number of chunks added, more than one with `--parts`
+    pub chunks_added: usize,
+    pub output: PathBuf,
+    pub bytes_written: usize,
+    /// Change in file size in bytes
+    pub size_delta: i64,
+    /// False when only the size delta or a dry run was requested
+    pub written: bool,
+    /// The file already had a Chunk of this type, which `decode` still shows first
+    pub duplicate: bool,
+    /// The message was appended to an existing Chunk, with `--append-to-existing`
+    pub appended: bool,
+}
+
+impl EncodeOutcome {
+    /// Warns that the file already had a chunk of this type, see `duplicate`
+    pub fn warning(&self) -> Option<String> {
+        self.duplicate.then(|| {
+            format!(
+                "the file already has a Chunk '{}' and decode only shows the first. \
+                 Use replace to update it or --no-duplicates to refuse",
+                self.chunk_type
+            )
+        })
+    }
+}
+
+impl fmt::Display for EncodeOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.appended {
+            let chunk = chunks_label(1, &self.chunk_type);
+            if !self.written {
+                return write!(
+                    f,
+                    "Appending to {} would change the file size by {:+} bytes",
+                    chunk, self.size_delta
+                );
+            }
+            return write!(f, "Message appended to {}", chunk);
+        }
+        let chunks = chunks_label(self.chunks_added, &self.chunk_type);
+        if !self.written {
+            return write!(
+                f,
+                "Adding {} would change the file size by {:+} bytes",
+                chunks, self.size_delta
+            );
+        }
+        write!(f, "{} added", chunks)
+    }
+}
+
+/// Names `count` chunks of `chunk_type`, as `Chunk 'X'` when there is only one
+fn chunks_label(count: usize, chunk_type: &str) -> String {
+    if count == 1 {
+        format!("Chunk '{}'", chunk_type)
+    } else {
+        format!("{} Chunk(s) '{}'", count, chunk_type)
+    }
+}
+
+/// What `remove` did: how many chunks of each type were removed
+#[derive(Debug, PartialEq, Eq)]
+pub struct RemoveOutcome {
+    pub removed: Vec<(String, usize)>,
+    /// Change in file size in bytes
+    pub size_delta: i64,
+    /// False when only the size delta or a dry run was requested
+    pub written: bool,
+}
+
+impl fmt::Display for RemoveOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verb = if self.written {
+            "removed"
+        } else {
+            "would be removed"
+        };
+        for (index, (chunk_type, count)) in self.removed.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{} {}", chunks_label(*count, chunk_type), verb)?;
+        }
+        if !self.written {
+            write!(
+                f,
+                "\nThe file size would change by {:+} bytes",
+                self.size_delta
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Change in size from `original` to `new` bytes
+fn size_delta(original: usize, new: usize) -> i64 {
+    new as i64 - original as i64
+}
+
+/// What `replace` did
+#[derive(Debug, PartialEq, Eq)]
+pub struct ReplaceOutcome {
+    pub chunk_type: String,
+    pub output: PathBuf,
+}
+
+impl fmt::Display for ReplaceOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Message of Chunk '{}' replaced", self.chunk_type)
+    }
+}
+
+/// What `rekey` did: how many chunks were obfuscated with the new key
+#[derive(Debug, PartialEq, Eq)]
+pub struct RekeyOutcome {
+    pub rekeyed: usize,
+    pub output: PathBuf,
+}
+
+impl fmt::Display for RekeyOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} Chunk(s) rekeyed", self.rekeyed)
+    }
+}
+
+/// What `dedup` did
+#[derive(Debug, PartialEq, Eq)]
+pub struct DedupOutcome {
+    pub removed: usize,
+}
+
+impl fmt::Display for DedupOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} duplicated Chunk(s) removed", self.removed)
+    }
+}
+
+/// What `namespace` did
+#[derive(Debug, PartialEq, Eq)]
+pub struct NamespaceOutcome {
+    pub renamed: usize,
+    pub prefix: String,
+}
+
+impl fmt::Display for NamespaceOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} Chunk(s) renamed under '{}'",
+            self.renamed, self.prefix
+        )
+    }
+}
+
+/// What `split` did: the files written, one per embedded PNG
+#[derive(Debug, PartialEq, Eq)]
+pub struct SplitOutcome {
+    pub files: Vec<PathBuf>,
+}
+
+impl fmt::Display for SplitOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} PNG file(s) written", self.files.len())
+    }
+}
+
+/// What `decode` found
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeOutcome {
+    /// There is no chunk of this type
+    NotFound(String),
+    /// The chunk types and messages that decode with `--try-all-with-key`
+    KeyMatches(Vec<(String, String)>),
+    /// The decoded messages, only one unless `--all`, after the keyword of a text chunk
+    Messages {
+        keyword: Option<String>,
+        messages: Vec<String>,
+    },
+    /// The message and its chunk described as JSON, see `message_json`
+    Json {
+        keyword: Option<String>,
+        json: serde_json::Value,
+    },
+    /// The message was written to this file
+    Written {
+        keyword: Option<String>,
+        output: PathBuf,
+    },
+}
+
+impl DecodeOutcome {
+    /// Returns true if this only reports what was done instead of holding the message
+    pub fn is_status(&self) -> bool {
+        matches!(self, DecodeOutcome::Written { .. })
+    }
+}
+
+impl fmt::Display for DecodeOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keyword = match self {
+            DecodeOutcome::NotFound(chunk_type) => {
+                return write!(f, "No message for Chunk '{}'", chunk_type);
+            }
+            DecodeOutcome::KeyMatches(matches) if matches.is_empty() => {
+                return write!(f, "No Chunk decodes to a readable message with this key");
+            }
+            DecodeOutcome::KeyMatches(matches) => {
+                let lines: Vec<String> = matches
+                    .iter()
+                    .map(|(chunk_type, message)| {
+                        format!("Message in Chunk '{}': {}", chunk_type, message)
+                    })
+                    .collect();
+                return write!(f, "{}", lines.join("\n"));
+            }
+            DecodeOutcome::Messages { keyword, .. }
+            | DecodeOutcome::Json { keyword, .. }
+            | DecodeOutcome::Written { keyword, .. } => keyword,
+        };
+        if let Some(keyword) = keyword {
+            writeln!(f, "Keyword: {}", keyword)?;
+        }
+        match self {
+            DecodeOutcome::Messages { messages, .. } => {
+                let lines: Vec<String> = messages
+                    .iter()
+                    .map(|message| format!("Message: {}", message))
+                    .collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            DecodeOutcome::Json { json, .. } => write!(f, "{:#}", json),
+            DecodeOutcome::Written { output, .. } => {
+                write!(f, "Message written to '{}'", output.display())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What `find` found
+#[derive(Debug)]
+pub struct FindOutcome {
+    /// The chunks that could hold a message, most likely first, each with why
+    pub candidates: Vec<(&'static str, Chunk)>,
+    /// Number of candidates left out by `--limit`
+    pub omitted: usize,
+}
+
+impl fmt::Display for FindOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.candidates.is_empty() && self.omitted == 0 {
+            return write!(f, "Couldn't find any possible Chunk with a message");
+        }
+        write!(f, "Chunks with possible messages: ")?;
+        for (reason, chunk) in self.candidates.iter() {
+            write!(f, "\n[{}] {}", reason, chunk)?;
+        }
+        if self.omitted > 0 {
+            write!(f, "\n... and {} more", self.omitted)?;
+        }
+        Ok(())
+    }
+}
+
+/// What `validate` checked, only returned when the file passed every check
+#[derive(Debug, PartialEq, Eq)]
+pub struct ValidateOutcome {
+    pub file: PathBuf,
+    /// Only the signature was checked, with `--verify-signature-only`
+    pub signature_only: bool,
+}
+
+impl fmt::Display for ValidateOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.signature_only {
+            return write!(f, "'{}' has a valid PNG signature", self.file.display());
+        }
+        write!(f, "'{}' is a valid PNG", self.file.display())
+    }
+}
+
+/// How likely `detect` found it that a file was modified by pngme
+#[derive(Debug, PartialEq, Eq)]
+pub struct DetectOutcome {
+    pub file: PathBuf,
+    /// From 0 to 100, see `pngme_score`
+    pub score: u8,
+}
+
+impl DetectOutcome {
+    /// Names the range the score falls in
+    pub fn confidence(&self) -> &'static str {
+        match self.score {
+            0 => "none",
+            1..=49 => "low",
+            50..=79 => "medium",
+            _ => "high",
+        }
+    }
+}
+
+impl fmt::Display for DetectOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Likelihood that '{}' was modified by pngme: {} ({}/100)",
+            self.file.display(),
+            self.confidence(),
+            self.score
+        )
+    }
+}
+
+/// What `print` shows of each PNG read, more than one with `--multi`
+#[derive(Debug)]
+pub struct PrintOutcome {
+    pub pngs: Vec<Png>,
+    /// Only the chunk types are shown, with `--types-only`
+    pub types_only: bool,
+    /// The chunks are shown as a JSON array, with `--json`
+    pub json: bool,
+    /// Each PNG is followed by the checksum of its file, with `--checksum`
+    pub checksum: bool,
+}
+
+impl fmt::Display for PrintOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines: Vec<String> = Vec::new();
+        for png in self.pngs.iter() {
+            if self.types_only {
+                lines.extend(chunk_types(png));
+            } else if self.json {
+                lines.push(serde_json::to_string_pretty(png.chunks()).map_err(|_| fmt::Error)?);
+            } else {
+                lines.push(png.to_string());
+            }
+            if self.checksum {
+                lines.push(format!("Checksum: {:08x}", png.file_checksum()));
+            }
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// What `exif` found in the eXIf chunk
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExifOutcome {
+    /// The size of the EXIF data and the byte order its header declares
+    Summary {
+        bytes: usize,
+        byte_order: &'static str,
+    },
+    /// The EXIF data was written to this file
+    Written(PathBuf),
+}
+
+impl ExifOutcome {
+    /// Returns true if this only reports what was done instead of describing the data
+    pub fn is_status(&self) -> bool {
+        matches!(self, ExifOutcome::Written(_))
+    }
+}
+
+impl fmt::Display for ExifOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExifOutcome::Summary { bytes, byte_order } => {
+                write!(f, "EXIF: {} bytes ({})", bytes, byte_order)
+            }
+            ExifOutcome::Written(file) => {
+                write!(f, "EXIF data written to '{}'", file.display())
+            }
+        }
+    }
+}
+
+/// What `extract` did
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExtractOutcome {
+    pub chunk_type: String,
+    pub output: PathBuf,
+    /// Number of chunks of this type in the file
+    pub found: usize,
+    /// Which of them was extracted, as given with `--index`
+    pub index: Option<usize>,
+}
+
+impl ExtractOutcome {
+    /// Warns that the first of several chunks was extracted because none was picked
+    pub fn warning(&self) -> Option<String> {
+        (self.index.is_none() && self.found > 1).then(|| {
+            format!(
+                "{} Chunks '{}' found, extracting the first. Use --index to pick another",
+                self.found, self.chunk_type
+            )
+        })
+    }
+}
+
+impl fmt::Display for ExtractOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Chunk '{}' extracted to '{}'",
+            self.chunk_type,
+            self.output.display()
+        )
+    }
+}
+
+/// The hash `fingerprint` computed over the image chunks
+#[derive(Debug, PartialEq, Eq)]
+pub struct FingerprintOutcome {
+    pub digest: Vec<u8>,
+}
+
+impl fmt::Display for FingerprintOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", to_hex(&self.digest))
+    }
+}
+
+/// The histogram `count` built, see `chunk_histogram`
+#[derive(Debug, PartialEq, Eq)]
+pub struct CountOutcome {
+    pub rows: Vec<(String, usize, usize)>,
+    /// The chunk count and data bytes of every row, with `--summary`
+    pub total: Option<(usize, usize)>,
+}
+
+impl fmt::Display for CountOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines: Vec<String> = self
+            .rows
+            .iter()
+            .map(|(chunk_type, count, bytes)| format!("{}\t{}\t{}", chunk_type, count, bytes))
+            .collect();
+        if let Some((count, bytes)) = self.total {
+            lines.push(format!("total\t{}\t{}", count, bytes));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// What `trailer` found after IEND or did with it
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrailerOutcome {
+    /// The file has this many bytes after IEND
+    Found { file: PathBuf, bytes: usize },
+    /// This many bytes after IEND were stripped, with `--strip`
+    Stripped(usize),
+}
+
+impl TrailerOutcome {
+    /// Returns true if this only reports what was done instead of describing the file
+    pub fn is_status(&self) -> bool {
+        matches!(self, TrailerOutcome::Stripped(_))
+    }
+}
+
+impl fmt::Display for TrailerOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrailerOutcome::Found { file, bytes } => {
+                write!(f, "'{}' has {} byte(s) after IEND", file.display(), bytes)
+            }
+            TrailerOutcome::Stripped(bytes) => {
+                write!(f, "{} byte(s) after IEND stripped", bytes)
+            }
+        }
+    }
+}
+
+/// What `info` read from a PNG
+#[derive(Debug, PartialEq, Eq)]
+pub struct InfoOutcome {
+    pub header: ImageHeader,
+    /// How often each palette entry is used, for an indexed-color image
+    pub palette: Option<Vec<u16>>,
+    /// Each chunk type with its count, in order of first appearance
+    pub chunks: Vec<(String, usize)>,
+}
+
+impl fmt::Display for InfoOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Dimensions: {}x{}",
+            self.header.width, self.header.height
+        )?;
+        writeln!(f, "Bit depth: {}", self.header.bit_depth)?;
+        writeln!(
+            f,
+            "Color type: {} ({})",
+            self.header.color_type,
+            self.header.color_type_name()
+        )?;
+        if let Some(ref histogram) = self.palette {
+            writeln!(f, "Palette histogram: {} entries", histogram.len())?;
+            for (entry, frequency) in histogram.iter().enumerate() {
+                writeln!(f, "\t{}\t{}", entry, frequency)?;
+            }
+        }
+        write!(f, "Chunks:")?;
+        for (chunk_type, count) in self.chunks.iter() {
+            write!(f, "\n\t{}\t{}", chunk_type, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// The chunks `list` shows
+#[derive(Debug, PartialEq, Eq)]
+pub enum ListOutcome {
+    /// A table with one line per chunk, see `list_lines`
+    Table(Vec<String>),
+    /// The chunks as a JSON array, with `--json`
+    Json(String),
+}
+
+impl fmt::Display for ListOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListOutcome::Table(lines) => {
+                write!(f, "{:>5}  TYPE  {:>10}  CRC       FLAGS", "INDEX", "LENGTH")?;
+                for line in lines {
+                    write!(f, "\n{}", line)?;
+                }
+                Ok(())
+            }
+            ListOutcome::Json(json) => write!(f, "{}", json),
+        }
+    }
+}
+
+/// What `repair` fixed
+#[derive(Debug, PartialEq, Eq)]
+pub struct RepairOutcome {
+    /// The chunks whose CRC was wrong
+    pub crc_errors: Vec<CrcMismatch>,
+    /// Bytes of a truncated chunk left out
+    pub dropped: usize,
+    /// Each wrong CRC is listed, with `--report`
+    pub report: bool,
+}
+
+impl fmt::Display for RepairOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.report {
+            for error in self.crc_errors.iter() {
+                writeln!(
+                    f,
+                    "Chunk {}: stored CRC {:08x}, computed {:08x}",
+                    error.index, error.stored, error.computed
+                )?;
+            }
+        }
+        if self.dropped > 0 {
+            writeln!(f, "{} byte(s) of a truncated Chunk dropped", self.dropped)?;
+        }
+        write!(f, "{} CRC(s) fixed", self.crc_errors.len())
+    }
+}
+
+/// What `capacity` counted
+#[derive(Debug, PartialEq, Eq)]
+pub struct CapacityOutcome {
+    pub capacity: Capacity,
+}
+
+impl fmt::Display for CapacityOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Custom ancillary Chunks: {}", self.capacity.chunks)?;
+        writeln!(f, "Bytes of data in them: {}", self.capacity.bytes)?;
+        write!(
+            f,
+            "Data limit per Chunk: {} bytes",
+            Capacity::MAX_CHUNK_BYTES
+        )
     }
-    Ok(())
 }
 
-/// Searches for a message hidden in a PNG file and prints the message if one is found
-pub fn find(args: FindArgs) -> Result<()> {
-    let png: Png = Png::from_file(&args.file)?;
+/// The differences `diff` found, see `diff_lines`
+#[derive(Debug, PartialEq, Eq)]
+pub struct DiffOutcome {
+    pub before: PathBuf,
+    pub after: PathBuf,
+    pub lines: Vec<String>,
+}
 
-    if let Some(chunks) = png.find_possible_messages() {
-        println!("Chunks with possible messages: ");
-        for &chunk in chunks.iter() {
-            println!("{}", chunk);
+impl fmt::Display for DiffOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.lines.is_empty() {
+            return write!(
+                f,
+                "'{}' and '{}' have the same Chunks",
+                self.before.display(),
+                self.after.display()
+            );
         }
+        write!(f, "{}", self.lines.join("\n"))
+    }
+}
+
+/// Encodes a message into a PNG file and saves the result.
+/// With `--in-place` a symlink is followed and its target is overwritten.
+pub fn encode(args: EncodeArgs, verbosity: Verbosity) -> Result<EncodeOutcome> {
+    let message_stdin = args.message.as_deref() == Some("-");
+    check_key_stdin(args.key_stdin, is_stdin(&args.file) || message_stdin)?;
+    let write = !args.size_delta && !args.dry_run;
+    if write {
+        let has_output = args.output.is_some() || args.output_template.is_some();
+        check_destination(&args.file, has_output, args.in_place)?;
+    }
+    let message = read_message(&args)?;
+    let message = if args.base64 {
+        decode_base64(&message)?
+    } else {
+        message
+    };
+    if message.is_empty() && !args.allow_empty {
+        return Err(Box::new(CommandError::EmptyMessage));
+    }
+    check_text_layout(&args.chunk_type, args.text, args.ztxt)?;
+    let chunk_type = ChunkType::from_str(&args.chunk_type)?;
+    let message = if args.ztxt {
+        ztxt_data(args.keyword.as_deref().unwrap_or_default(), &message)?
+    } else if args.text {
+        text_data(args.keyword.as_deref().unwrap_or_default(), &message)?
+    } else if args.compress {
+        compress(&message)?
     } else {
-        println!("Couldn't find any possible Chunk with a message");
+        message
+    };
+    let cipher = args.cipher.unwrap_or(CipherKind::Xor);
+    let data = match read_key(args.key.clone(), args.key_stdin, args.cipher)? {
+        Some(ref key) => encrypt(&message, key, cipher)?,
+        None => message,
+    };
+
+    let (mut duplicate, mut appended) = (false, false);
+    let (original_len, bytes): (usize, Vec<u8>) = match args.at_offset {
+        Some(offset) => {
+            let new_chunk = Chunk::new_checked(chunk_type, data)?;
+            let original = read_input(&args.file)?;
+            duplicate = Png::stream_chunks(original.as_slice())?
+                .map_while(|chunk| chunk.ok())
+                .any(|chunk| chunk.chunk_type() == new_chunk.chunk_type());
+            if duplicate && args.no_duplicate {
+                return Err(Box::new(CommandError::DuplicateChunk(args.chunk_type)));
+            }
+            (original.len(), splice_chunk(original, offset, &new_chunk)?)
+        }
+        None => {
+            let mut png: Png = load_png(&args.file)?;
+            let original_len = png.to_file_bytes()?.len();
+            let existing = png
+                .iter()
+                .position(|chunk| chunk.chunk_type() == &chunk_type);
+            match existing {
+                Some(index) if args.append_to_existing => {
+                    png.extend_chunk_data(index, &data)?;
+                    appended = true;
+                }
+                Some(_) if args.no_duplicate => {
+                    return Err(Box::new(CommandError::DuplicateChunk(args.chunk_type)));
+                }
+                existing => {
+                    duplicate = existing.is_some();
+                    let data = if args.spread {
+                        spread_data(&mut png, &data)?
+                    } else {
+                        data
+                    };
+                    let parts = match args.parts {
+                        Some(parts) => split_parts(&data, parts.get())?,
+                        None => vec![data.as_slice()],
+                    };
+                    for (i, part) in parts.into_iter().enumerate() {
+                        let chunk = Chunk::new_checked(chunk_type.clone(), part.to_vec())?;
+                        if let Some(index) = args.at {
+                            png.insert_chunk(index + i, chunk)?;
+                        } else if args.apng {
+                            png.insert_before_animation(chunk);
+                        } else {
+                            png.append_chunk(chunk);
+                        }
+                    }
+                }
+            }
+            print_details(&png, Some(&args.chunk_type), None, verbosity);
+            (original_len, png.to_file_bytes()?)
+        }
+    };
+    if let Some(max) = args.max_growth {
+        check_growth(original_len, bytes.len(), max)?;
+    }
+
+    let output = output_path(
+        &args.file,
+        args.output.as_deref(),
+        args.output_template.as_deref(),
+    );
+    if write {
+        write_file(&output, &bytes)?;
+        if let Some(log) = args.log {
+            audit::record(&log, "encode", &output, &args.chunk_type)?;
+        }
+    }
+    Ok(EncodeOutcome {
+        chunk_type: args.chunk_type,
+        chunks_added: match args.parts {
+            _ if appended => 0,
+            Some(parts) => parts.get(),
+            None => 1,
+        },
+        output,
+        bytes_written: bytes.len(),
+        size_delta: size_delta(original_len, bytes.len()),
+        written: write,
+        duplicate,
+        appended,
+    })
+}
+
+/// Fails when growing a file from `original` to `new` bytes exceeds `max` percent
+fn check_growth(original: usize, new: usize, max: f64) -> Result<()> {
+    let growth = (new as f64 - original as f64) / original as f64 * 100.0;
+    if growth > max {
+        return Err(Box::new(CommandError::GrowthExceeded(growth, max)));
     }
     Ok(())
 }
 
-/// Removes a chunk from a PNG file and saves the result
-pub fn remove(args: RemoveArgs) -> Result<()> {
-    let mut png: Png = Png::from_file(&args.file)?;
-    png.remove_chunk(&args.chunk_type)?;
+/// Splits `data` into `parts` slices whose sizes differ by at most one byte, the
+/// first ones taking one more byte each when `data` doesn't divide evenly. Fails
+/// when there are more parts than bytes, since some parts would be empty.
+fn split_parts(data: &[u8], parts: usize) -> Result<Vec<&[u8]>> {
+    if parts > data.len() {
+        return Err(Box::new(CommandError::TooManyParts(parts, data.len())));
+    }
+    let (size, remainder) = (data.len() / parts, data.len() % parts);
+    let mut split: Vec<&[u8]> = Vec::with_capacity(parts);
+    let mut rest = data;
+    for index in 0..parts {
+        let (part, tail) = rest.split_at(size + usize::from(index < remainder));
+        split.push(part);
+        rest = tail;
+    }
+    Ok(split)
+}
+
+/// Reads the bytes to encode from the message file or environment variable, stdin
+/// when the message is `-`, or else the message itself
+fn read_message(args: &EncodeArgs) -> Result<Vec<u8>> {
+    if let Some(ref path) = args.message_file {
+        return Ok(fs::read(path)?);
+    }
+    if let Some(ref name) = args.message_env {
+        return match std::env::var(name) {
+            Ok(message) => Ok(message.into_bytes()),
+            Err(_) => Err(Box::new(CommandError::MissingEnvVar(name.clone()))),
+        };
+    }
+    match args.message.as_deref() {
+        Some("-") => {
+            let mut message = Vec::new();
+            io::stdin().read_to_end(&mut message)?;
+            Ok(message)
+        }
+        message => Ok(message.unwrap_or_default().as_bytes().to_vec()),
+    }
+}
+
+/// Decodes base64 `text`, ignoring surrounding whitespace
+fn decode_base64(text: &[u8]) -> Result<Vec<u8>> {
+    match base64::engine::general_purpose::STANDARD.decode(text.trim_ascii()) {
+        Ok(bytes) => Ok(bytes),
+        Err(err) => Err(Box::new(CommandError::InvalidBase64(err.to_string()))),
+    }
+}
 
-    fs::write(args.file, png.as_bytes())?;
-    println!("Chunk '{}' removed", args.chunk_type);
+/// Fails when a modified file has nowhere to go: stdin needs an output, and
+/// overwriting the input file needs `in_place`
+fn check_destination(file: &Path, has_output: bool, in_place: bool) -> Result<()> {
+    if has_output {
+        return Ok(());
+    }
+    if is_stdin(file) {
+        return Err(Box::new(CommandError::StdinWithoutOutput));
+    }
+    if !in_place {
+        return Err(Box::new(CommandError::InPlaceRequired(file.to_path_buf())));
+    }
     Ok(())
 }
 
-/// Prints all of the chunks in a PNG file
-pub fn print_chunks(args: PrintArgs) -> Result<()> {
-    let png: Png = Png::from_file(&args.file)?;
-    println!("{}", png);
+/// Picks where a modified file is written: the explicit `output`, the expanded
+/// output `template` or else the input `file` itself
+fn output_path(file: &Path, output: Option<&Path>, template: Option<&str>) -> PathBuf {
+    match (output, template) {
+        (Some(output), _) => output.to_path_buf(),
+        (None, Some(template)) => expand_output_template(template, file),
+        (None, None) => file.to_path_buf(),
+    }
+}
+
+/// Replaces the `{dir}`, `{name}` and `{ext}` placeholders in `template` with the
+/// parent directory, file stem and extension of `file`
+fn expand_output_template(template: &str, file: &Path) -> PathBuf {
+    let dir = match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy(),
+        _ => ".".into(),
+    };
+    let name = file.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = file.extension().unwrap_or_default().to_string_lossy();
+
+    PathBuf::from(
+        template
+            .replace("{dir}", &dir)
+            .replace("{name}", &name)
+            .replace("{ext}", &ext),
+    )
+}
+
+/// Reads a PNG file, accepting any four bytes as a chunk type with `no_validate_type`
+fn read_png(file: &Path, no_validate_type: bool) -> Result<Png> {
+    if no_validate_type {
+        load_png_with(file, Png::from_bytes_raw_types)
+    } else {
+        load_png(file)
+    }
+}
+
+/// Returns true if `file` is `-`, which stands for stdin
+fn is_stdin(file: &Path) -> bool {
+    file.as_os_str() == "-"
+}
+
+/// Reads the PNG file at `file`, or from stdin when `file` is `-`
+fn load_png(file: &Path) -> Result<Png> {
+    load_png_from(file, io::stdin().lock())
+}
+
+/// Reads the PNG file at `file`, or from `stdin` when `file` is `-`
+fn load_png_from<R: Read>(file: &Path, stdin: R) -> Result<Png> {
+    if is_stdin(file) {
+        Ok(Png::from_reader(stdin)?)
+    } else {
+        Ok(Png::from_file(file)?)
+    }
+}
+
+/// Reads a PNG file like `load_png`, or the one embedded in it at byte `offset`
+fn load_png_at(file: &Path, offset: Option<usize>) -> Result<Png> {
+    match offset {
+        Some(offset) => Ok(Png::from_bytes_embedded(&read_input(file)?, offset)?),
+        None => load_png(file),
+    }
+}
+
+/// Reads a PNG file like `load_png`, but builds the `Png` from its bytes with `parse`
+fn load_png_with(file: &Path, parse: impl FnOnce(&[u8]) -> pngme::Result<Png>) -> Result<Png> {
+    if is_stdin(file) {
+        Ok(parse(&read_input(file)?)?)
+    } else {
+        Ok(Png::from_file_with(file, parse)?)
+    }
+}
+
+/// Reads all of the bytes of `file`, or of stdin when `file` is `-`
+fn read_input(file: &Path) -> Result<Vec<u8>> {
+    if !is_stdin(file) {
+        return Ok(fs::read(file)?);
+    }
+    let mut bytes = Vec::new();
+    io::stdin().read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Opens `file` for reading, or stdin when `file` is `-`
+fn open_input(file: &Path) -> Result<Box<dyn Read>> {
+    if is_stdin(file) {
+        Ok(Box::new(io::stdin()))
+    } else {
+        Ok(Box::new(fs::File::open(file)?))
+    }
+}
+
+/// Opens `file` like `open_input`, skipping the first `offset` bytes if given
+fn open_input_at(file: &Path, offset: Option<usize>) -> Result<Box<dyn Read>> {
+    let mut reader = open_input(file)?;
+    if let Some(offset) = offset {
+        io::copy(&mut reader.by_ref().take(offset as u64), &mut io::sink())?;
+    }
+    Ok(reader)
+}
+
+/// Returns the bytes of a file from `offset` on, where an embedded PNG starts
+fn bytes_from(bytes: &[u8], offset: Option<usize>) -> Result<&[u8]> {
+    Ok(bytes.get(offset.unwrap_or(0)..).ok_or(PngError::TooSmall)?)
+}
+
+/// Writes `bytes` to `path`, creating any missing parent directories
+fn write_file(path: &Path, bytes: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    fs::write(path, bytes)?;
     Ok(())
 }
+
+/// Prints a status message, such as the outcome of a command, unless `verbosity` is quiet
+pub fn report<T: fmt::Display>(message: T, verbosity: Verbosity) {
+    if let Some(line) = status_line(message, verbosity) {
+        println!("{}", line);
+    }
+}
+
+/// Prints a warning to stderr unless `verbosity` is quiet
+pub fn warn(warning: Option<String>, verbosity: Verbosity) {
+    if let Some(warning) = warning.filter(|_| verbosity != Verbosity::Quiet) {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+/// Formats a status message, `None` when `verbosity` is quiet
+fn status_line<T: fmt::Display>(message: T, verbosity: Verbosity) -> Option<String> {
+    (verbosity != Verbosity::Quiet).then(|| message.to_string())
+}
+
+/// Prints the details of the chunks of `png`, see `detail_lines`
+fn print_details(png: &Png, chunk_type: Option<&str>, start: Option<usize>, verbosity: Verbosity) {
+    for line in detail_lines(png, chunk_type, start, verbosity) {
+        println!("{}", line);
+    }
+}
+
+/// Formats the position, offset in the file, size and CRC of each chunk of `png`,
+/// only those of `chunk_type` if given. `start` is where `png` starts in the file,
+/// see `--offset`. Empty unless `verbosity` is verbose.
+fn detail_lines(
+    png: &Png,
+    chunk_type: Option<&str>,
+    start: Option<usize>,
+    verbosity: Verbosity,
+) -> Vec<String> {
+    if verbosity != Verbosity::Verbose {
+        return Vec::new();
+    }
+    let mut offset = start.unwrap_or(0) + Png::HEADER_LENGHT;
+    let mut lines = Vec::new();
+    for (index, chunk) in png.iter().enumerate() {
+        if chunk_type.is_none_or(|chunk_type| chunk_type == chunk.chunk_type().to_string()) {
+            lines.push(detail_line(index, chunk, offset));
+        }
+        offset += Chunk::MIN_BYTES + chunk.data().len();
+    }
+    lines
+}
+
+/// Formats the details of the chunk at `index`, found at byte `offset` of the file
+fn detail_line(index: usize, chunk: &Chunk, offset: usize) -> String {
+    format!(
+        "Chunk {} '{}' at offset {}: {} byte(s) of data, CRC {:08x}",
+        index,
+        chunk.chunk_type(),
+        offset,
+        chunk.length(),
+        chunk.crc()
+    )
+}
+
+/// Picks the key to encrypt with: the one given with `--key`, the first line of stdin
+/// with `--key-stdin`, or else one typed at a prompt when a cipher was requested
+fn read_key(
+    key: Option<String>,
+    key_stdin: bool,
+    cipher: Option<CipherKind>,
+) -> Result<Option<String>> {
+    if key_stdin {
+        return read_key_line(io::stdin().lock()).map(Some);
+    }
+    match (key, cipher) {
+        (Some(key), _) => Ok(Some(key)),
+        (None, Some(_)) => Ok(Some(rpassword::prompt_password("Key: ")?)),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Refuses `--key-stdin` when the PNG or the message is read from stdin too, as
+/// the key would then be read from their bytes or from an exhausted stdin
+fn check_key_stdin(key_stdin: bool, stdin_taken: bool) -> Result<()> {
+    if key_stdin && stdin_taken {
+        return Err(Box::new(CommandError::StdinTaken));
+    }
+    Ok(())
+}
+
+/// Reads a key from the first line of `reader`, without the line ending
+fn read_key_line<R: io::BufRead>(mut reader: R) -> Result<String> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let key = line.trim_end_matches(['\n', '\r']);
+    if key.is_empty() {
+        return Err(Box::new(CommandError::EmptyKey));
+    }
+    Ok(key.to_string())
+}
+
+/// Inserts the raw bytes of `chunk` into `bytes` at `offset` without any regard
+/// for chunk boundaries. Used to craft malformed files for testing other parsers.
+fn splice_chunk(mut bytes: Vec<u8>, offset: usize, chunk: &Chunk) -> Result<Vec<u8>> {
+    if offset > bytes.len() {
+        return Err(Box::new(CommandError::OffsetOutOfRange(
+            offset,
+            bytes.len(),
+        )));
+    }
+    bytes.splice(offset..offset, chunk.as_bytes());
+    Ok(bytes)
+}
+
+/// Searches for a message hidden in a PNG file and returns the message if one is found
+pub fn decode(args: DecodeArgs, verbosity: Verbosity) -> Result<DecodeOutcome> {
+    check_key_stdin(args.key_stdin, is_stdin(&args.file))?;
+    let png: Png = load_png_at(&args.file, args.offset)?;
+
+    if let Some(key) = args.try_all_with_key {
+        let matches = decode_all_with_key(&png, &key)
+            .into_iter()
+            .map(|(chunk, message)| (chunk.chunk_type().to_string(), message))
+            .collect();
+        return Ok(DecodeOutcome::KeyMatches(matches));
+    }
+
+    let cipher = args.cipher.unwrap_or(CipherKind::Xor);
+    let key = read_key(args.key, args.key_stdin, args.cipher)?;
+    let chunk_type = args.chunk_type.unwrap_or_default();
+    check_text_layout(&chunk_type, args.text, args.ztxt)?;
+    if args.all {
+        let chunks: Vec<&Chunk> = png
+            .iter()
+            .filter(|chunk| type_matches(chunk, &chunk_type, args.ignore_case))
+            .collect();
+        let mut messages: Vec<String> = Vec::new();
+        for chunk in chunks.iter() {
+            let message = unwrap_message(chunk.data(), key.as_deref(), cipher, args.compress)?;
+            let message = message_text(message, args.base64, args.encoding)?;
+            if contains(&message, args.grep.as_deref()) {
+                messages.push(message);
+            }
+        }
+        return match args.grep {
+            Some(pattern) if messages.is_empty() => Err(Box::new(CommandError::NoMatch(pattern))),
+            _ if chunks.is_empty() => Ok(DecodeOutcome::NotFound(chunk_type)),
+            _ => Ok(DecodeOutcome::Messages {
+                keyword: None,
+                messages,
+            }),
+        };
+    }
+
+    let chunk_type = matching_types(&png, &chunk_type, args.ignore_case).swap_remove(0);
+
+    let Some(chunk) = png.chunk_by_type(&chunk_type) else {
+        return match args.grep {
+            Some(pattern) => Err(Box::new(CommandError::NoMatch(pattern))),
+            None => Ok(DecodeOutcome::NotFound(chunk_type)),
+        };
+    };
+    print_details(&png, Some(&chunk_type), args.offset, verbosity);
+    let data: Vec<u8> = if args.spread {
+        gather_data(&png, chunk.data())?
+    } else {
+        chunk.data().to_vec()
+    };
+
+    let (keyword, message) = if args.ztxt {
+        let (keyword, text) = parse_ztxt(&data)?;
+        (Some(keyword), latin1_string(&text).into_bytes())
+    } else if args.text {
+        let (keyword, text) = split_keyword(&data)?;
+        (Some(keyword), latin1_string(text).into_bytes())
+    } else {
+        let message = unwrap_message(&data, key.as_deref(), cipher, args.compress)?;
+        (None, message)
+    };
+    if args.json {
+        let json = message_json(chunk, message);
+        return Ok(DecodeOutcome::Json { keyword, json });
+    }
+    if let Some(output) = args.output {
+        fs::write(&output, message)?;
+        return Ok(DecodeOutcome::Written { keyword, output });
+    }
+
+    let message = message_text(message, args.base64, args.encoding)?;
+    if !contains(&message, args.grep.as_deref()) {
+        return Err(Box::new(CommandError::NoMatch(
+            args.grep.unwrap_or_default(),
+        )));
+    }
+    Ok(DecodeOutcome::Messages {
+        keyword,
+        messages: vec![message],
+    })
+}
+
+/// Describes a decoded message and the chunk it came from as a JSON object. The
+/// message is under `message` when it is valid UTF-8 and `base64` otherwise.
+fn message_json(chunk: &Chunk, message: Vec<u8>) -> serde_json::Value {
+    let mut json = serde_json::json!({
+        "type": chunk.chunk_type().to_string(),
+        "length": chunk.length(),
+        "crc": chunk.crc(),
+    });
+    match String::from_utf8(message) {
+        Ok(text) => {
+            json["utf8"] = true.into();
+            json["message"] = text.into();
+        }
+        Err(err) => {
+            json["utf8"] = false.into();
+            json["base64"] = base64::engine::general_purpose::STANDARD
+                .encode(err.into_bytes())
+                .into();
+        }
+    }
+    json
+}
+
+/// Formats the bytes of a message for printing, as base64 with `base64` and
+/// otherwise as text in `encoding`
+fn message_text(message: Vec<u8>, base64: bool, encoding: TextEncoding) -> Result<String> {
+    if base64 {
+        return Ok(base64::engine::general_purpose::STANDARD.encode(message));
+    }
+    decode_text(message, encoding)
+}
+
+/// Interprets the bytes of a message as text in `encoding`
+fn decode_text(message: Vec<u8>, encoding: TextEncoding) -> Result<String> {
+    match encoding {
+        TextEncoding::Utf8 => Ok(String::from_utf8(message)?),
+        #[cfg(feature = "encoding")]
+        TextEncoding::Latin1 => decode_with(encoding_rs::WINDOWS_1252, &message),
+        #[cfg(feature = "encoding")]
+        TextEncoding::Utf16le => decode_with(encoding_rs::UTF_16LE, &message),
+        #[cfg(feature = "encoding")]
+        TextEncoding::Utf16be => decode_with(encoding_rs::UTF_16BE, &message),
+        #[cfg(not(feature = "encoding"))]
+        TextEncoding::Latin1 | TextEncoding::Utf16le | TextEncoding::Utf16be => {
+            Err(Box::new(CommandError::MissingFeature("encoding")))
+        }
+    }
+}
+
+/// Decodes `message` with `encoding`, failing on malformed sequences instead of
+/// replacing them
+#[cfg(feature = "encoding")]
+fn decode_with(encoding: &'static encoding_rs::Encoding, message: &[u8]) -> Result<String> {
+    let (text, had_errors) = encoding.decode_without_bom_handling(message);
+    if had_errors {
+        return Err(Box::new(CommandError::InvalidText(encoding.name())));
+    }
+    Ok(text.into_owned())
+}
+
+/// Returns true if there is no `pattern` or `message` contains it
+fn contains(message: &str, pattern: Option<&str>) -> bool {
+    pattern.is_none_or(|pattern| message.contains(pattern))
+}
+
+/// Reverts what `encode` did to a message: removes the XOR obfuscation when a `key`
+/// is given and then decompresses it if it was `compressed`
+fn unwrap_message(
+    data: &[u8],
+    key: Option<&str>,
+    cipher: CipherKind,
+    compressed: bool,
+) -> Result<Vec<u8>> {
+    let data = match key {
+        Some(key) => decrypt(data, key, cipher)?,
+        None => data.to_vec(),
+    };
+    if compressed {
+        decompress(&data)
+    } else {
+        Ok(data)
+    }
+}
+
+/// Obfuscates or encrypts `data` with `key` using `cipher`
+fn encrypt(data: &[u8], key: &str, cipher: CipherKind) -> Result<Vec<u8>> {
+    match cipher {
+        CipherKind::Xor => Ok(cipher::xor_encode(data, key)),
+        #[cfg(feature = "aes")]
+        CipherKind::Aes => Ok(cipher::aes_encrypt(data, key)),
+        #[cfg(not(feature = "aes"))]
+        CipherKind::Aes => Err(Box::new(CommandError::MissingFeature("aes"))),
+    }
+}
+
+/// Reverses `encrypt`
+fn decrypt(data: &[u8], key: &str, cipher: CipherKind) -> Result<Vec<u8>> {
+    match cipher {
+        CipherKind::Xor => Ok(cipher::xor_encode(data, key)),
+        #[cfg(feature = "aes")]
+        CipherKind::Aes => Ok(cipher::aes_decrypt(data, key)?),
+        #[cfg(not(feature = "aes"))]
+        CipherKind::Aes => Err(Box::new(CommandError::MissingFeature("aes"))),
+    }
+}
+
+/// Compresses `data` into a zlib stream
+fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Decompresses a zlib stream made by `compress`
+fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut decompressed: Vec<u8> = Vec::new();
+    ZlibDecoder::new(data)
+        .read_to_end(&mut decompressed)
+        .map_err(|_| CommandError::NotCompressed)?;
+    Ok(decompressed)
+}
+
+/// Compression method byte of a zTXt chunk, zlib deflate is the only one defined
+const ZTXT_DEFLATE: u8 = 0;
+
+/// Lays out the data of a zTXt chunk: the Latin-1 `keyword`, a null separator, the
+/// compression method and the UTF-8 `text` encoded as Latin-1 and compressed with zlib
+fn ztxt_data(keyword: &str, text: &[u8]) -> Result<Vec<u8>> {
+    let mut data = keyword_bytes(keyword)?;
+    data.push(0);
+    data.push(ZTXT_DEFLATE);
+    data.extend(compress(&latin1_text(text)?)?);
+    Ok(data)
+}
+
+/// Lays out the data of a tEXt chunk: the Latin-1 `keyword`, a null separator and
+/// the UTF-8 `text` encoded as Latin-1, which can't contain a null itself
+fn text_data(keyword: &str, text: &[u8]) -> Result<Vec<u8>> {
+    if text.contains(&0) {
+        return Err(Box::new(CommandError::InvalidTextChunk(
+            "the text contains a null byte",
+        )));
+    }
+    let mut data = keyword_bytes(keyword)?;
+    data.push(0);
+    data.extend(latin1_text(text)?);
+    Ok(data)
+}
+
+/// Checks that the tEXt layout with `text` or the zTXt layout with `ztxt` is only
+/// used with a chunk of that type
+fn check_text_layout(chunk_type: &str, text: bool, ztxt: bool) -> Result<()> {
+    let expected = match (text, ztxt) {
+        (true, _) => "tEXt",
+        (_, true) => "zTXt",
+        _ => return Ok(()),
+    };
+    if chunk_type != expected {
+        return Err(Box::new(CommandError::TextLayoutMismatch(
+            expected,
+            chunk_type.to_string(),
+        )));
+    }
+    Ok(())
+}
+
+/// Encodes the UTF-8 `text` of a text chunk as Latin-1, failing on any character
+/// outside of it
+fn latin1_text(text: &[u8]) -> Result<Vec<u8>> {
+    std::str::from_utf8(text)
+        .ok()
+        .and_then(latin1_bytes)
+        .ok_or_else(|| CommandError::InvalidText("Latin-1").into())
+}
+
+/// Encodes `text` as Latin-1, `None` if a character is outside of it
+fn latin1_bytes(text: &str) -> Option<Vec<u8>> {
+    text.chars().map(|c| u8::try_from(c).ok()).collect()
+}
+
+/// Decodes Latin-1 `bytes`, where every byte is the character with that code
+fn latin1_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| char::from(byte)).collect()
+}
+
+/// Splits the data of a zTXt chunk into its keyword and inflated text
+fn parse_ztxt(data: &[u8]) -> Result<(String, Vec<u8>)> {
+    let (keyword, rest) = split_keyword(data)?;
+    match rest.split_first() {
+        Some((&ZTXT_DEFLATE, compressed)) => Ok((keyword, decompress(compressed)?)),
+        _ => Err(Box::new(CommandError::InvalidTextChunk(
+            "unknown compression method",
+        ))),
+    }
+}
+
+/// Splits the data of a text chunk at the null byte ending its Latin-1 keyword
+fn split_keyword(data: &[u8]) -> Result<(String, &[u8])> {
+    let separator =
+        data.iter()
+            .position(|&byte| byte == 0)
+            .ok_or(CommandError::InvalidTextChunk(
+                "no null byte after the keyword",
+            ))?;
+    Ok((latin1_string(&data[..separator]), &data[separator + 1..]))
+}
+
+/// Encodes a text chunk keyword as Latin-1, checking it is 1 to 79 printable
+/// characters without leading, trailing or consecutive spaces as the spec requires
+fn keyword_bytes(keyword: &str) -> Result<Vec<u8>> {
+    let printable = |byte: &u8| matches!(byte, 32..=126 | 161..=255);
+    match latin1_bytes(keyword) {
+        Some(bytes)
+            if (1..=79).contains(&bytes.len())
+                && bytes.iter().all(printable)
+                && !keyword.starts_with(' ')
+                && !keyword.ends_with(' ')
+                && !keyword.contains("  ") =>
+        {
+            Ok(bytes)
+        }
+        _ => Err(Box::new(CommandError::InvalidKeyword(keyword.to_string()))),
+    }
+}
+
+/// Bytes of each entry in the layout of a spread message, see `spread_data`
+const LAYOUT_ENTRY_BYTES: usize = 12;
+
+/// Splits `data` evenly across the custom ancillary chunks of `png`, appending each
+/// part to a chunk's data. Standard chunks are never used since their data has a
+/// fixed layout. Returns the layout needed by `gather_data`: for every chunk that was
+/// extended its type, a big-endian `u32` telling which chunk of that type it is and
+/// a `u32` part length. Chunks added or removed later don't break the layout.
+fn spread_data(png: &mut Png, data: &[u8]) -> Result<Vec<u8>> {
+    let mut carriers: Vec<(usize, ChunkType, u32)> = Vec::new();
+    for (index, chunk) in png.iter().enumerate() {
+        let chunk_type = chunk.chunk_type();
+        if chunk_type.is_critical() || Png::is_standard_type(&chunk_type.to_string()) {
+            continue;
+        }
+        let occurrence = carriers
+            .iter()
+            .filter(|(_, carrier_type, _)| carrier_type == chunk_type)
+            .count();
+        carriers.push((index, chunk_type.clone(), occurrence as u32));
+    }
+    if carriers.is_empty() {
+        return Err(Box::new(CommandError::NoCarrierChunks));
+    }
+
+    let part_size = data.len().div_ceil(carriers.len()).max(1);
+    let mut layout: Vec<u8> = Vec::new();
+    for ((index, chunk_type, occurrence), part) in carriers.iter().zip(data.chunks(part_size)) {
+        png.extend_chunk_data(*index, part)?;
+        layout.extend_from_slice(&chunk_type.bytes());
+        layout.extend_from_slice(&occurrence.to_be_bytes());
+        layout.extend_from_slice(&(part.len() as u32).to_be_bytes());
+    }
+    Ok(layout)
+}
+
+/// Reassembles the data spread by `spread_data` from the tails of the chunks
+/// listed in `layout`
+fn gather_data(png: &Png, layout: &[u8]) -> Result<Vec<u8>> {
+    if !layout.len().is_multiple_of(LAYOUT_ENTRY_BYTES) {
+        return Err(Box::new(CommandError::InvalidLayout));
+    }
+
+    let mut data: Vec<u8> = Vec::new();
+    for entry in layout.chunks(LAYOUT_ENTRY_BYTES) {
+        let chunk_type =
+            std::str::from_utf8(&entry[..4]).map_err(|_| CommandError::InvalidLayout)?;
+        let occurrence = u32::from_be_bytes(entry[4..8].try_into()?) as usize;
+        let length = u32::from_be_bytes(entry[8..].try_into()?) as usize;
+        let carrier = *png
+            .chunks_by_type(chunk_type)
+            .get(occurrence)
+            .ok_or(CommandError::InvalidLayout)?;
+        let carrier = carrier.data();
+        let start = carrier
+            .len()
+            .checked_sub(length)
+            .ok_or(CommandError::InvalidLayout)?;
+        data.extend_from_slice(&carrier[start..]);
+    }
+    Ok(data)
+}
+
+/// XOR-decodes every ancillary chunk with `key` and returns the chunks whose
+/// result is non-empty, printable UTF-8 along with the decoded message
+fn decode_all_with_key<'a>(png: &'a Png, key: &str) -> Vec<(&'a Chunk, String)> {
+    png.chunks()
+        .iter()
+        .filter_map(|chunk| Some((chunk, readable_with_key(chunk, key)?)))
+        .collect()
+}
+
+/// XOR-decodes an ancillary chunk with `key`, returning the message when it is
+/// non-empty, printable UTF-8
+fn readable_with_key(chunk: &Chunk, key: &str) -> Option<String> {
+    if chunk.chunk_type().is_critical() {
+        return None;
+    }
+    let message = String::from_utf8(cipher::xor_encode(chunk.data(), key)).ok()?;
+    let printable = !message.is_empty()
+        && message
+            .chars()
+            .all(|c| !c.is_control() || c.is_ascii_whitespace());
+    printable.then_some(message)
+}
+
+/// Obfuscates again with `new_key` every custom ancillary chunk that decodes to a
+/// readable message with `old_key`, leaving the others alone. Standard chunks such as
+/// tEXt are never touched. Returns how many were rekeyed.
+fn rekey_chunks(png: &mut Png, old_key: &str, new_key: &str) -> Result<usize> {
+    if old_key.is_empty() {
+        return Err(Box::new(CommandError::EmptyOldKey));
+    }
+    let rekeyed: Vec<(usize, Chunk)> = png
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| !Png::is_standard_type(&chunk.chunk_type().to_string()))
+        .filter_map(|(index, chunk)| {
+            let message = readable_with_key(chunk, old_key)?;
+            let data = cipher::xor_encode(message.as_bytes(), new_key);
+            Some((index, Chunk::new(chunk.chunk_type().clone(), data)))
+        })
+        .collect();
+    let count = rekeyed.len();
+    for (index, chunk) in rekeyed {
+        png.replace_chunk(index, chunk)?;
+    }
+    Ok(count)
+}
+
+/// Rotates the XOR key of the messages in a PNG file and saves the result
+pub fn rekey(args: RekeyArgs) -> Result<RekeyOutcome> {
+    check_destination(&args.file, args.output.is_some(), args.in_place)?;
+    let mut png: Png = load_png(&args.file)?;
+    let rekeyed = rekey_chunks(&mut png, &args.old_key, &args.new_key)?;
+
+    let output = args.output.unwrap_or(args.file);
+    write_file(&output, &png.to_file_bytes()?)?;
+    Ok(RekeyOutcome { rekeyed, output })
+}
+
+/// Searches a PNG file for chunks that could hold a hidden message and returns them.
+/// The chunks are read as a stream, so memory stays bounded by `--limit` however large
+/// the file is: the first `--limit` candidates in file order are kept and the others
+/// only counted. The kept candidates are then ranked, most likely first, unless
+/// `--stream` asks for them in file order.
+pub fn find(args: FindArgs, verbosity: Verbosity) -> Result<FindOutcome> {
+    let reader = open_input_at(&args.file, args.offset)?;
+
+    let mut kept: Vec<(u8, &'static str, Chunk)> = Vec::new();
+    let mut omitted = 0;
+    let mut offset = args.offset.unwrap_or(0) + Png::HEADER_LENGHT;
+    for (index, chunk) in Png::stream_chunks(reader)?.enumerate() {
+        let chunk = chunk?;
+        let chunk_offset = offset;
+        offset += Chunk::MIN_BYTES + chunk.data().len();
+        let Some(candidate) = PossibleMessage::new(&chunk) else {
+            continue;
+        };
+        if !find_keeps(&candidate, &args) {
+            continue;
+        }
+        if args.limit.is_none_or(|limit| kept.len() < limit) {
+            if verbosity == Verbosity::Verbose {
+                println!("{}", detail_line(index, &chunk, chunk_offset));
+            }
+            let (rank, reason) = (candidate.rank(), candidate.reason());
+            kept.push((rank, reason, chunk));
+        } else {
+            omitted += 1;
+        }
+    }
+    if !args.stream {
+        kept.sort_by_key(|(rank, _, _)| std::cmp::Reverse(*rank));
+    }
+    Ok(FindOutcome {
+        candidates: kept
+            .into_iter()
+            .map(|(_, reason, chunk)| (reason, chunk))
+            .collect(),
+        omitted,
+    })
+}
+
+/// Returns true if `candidate` passes the `--utf8-only` and `--type` filters of `find`
+fn find_keeps(candidate: &PossibleMessage, args: &FindArgs) -> bool {
+    let type_ok = args
+        .chunk_type
+        .as_ref()
+        .is_none_or(|chunk_type| type_matches(candidate.chunk, chunk_type, args.ignore_case));
+    type_ok && (candidate.utf8 || !args.utf8_only)
+}
+
+/// Returns true if `chunk` has the type `chunk_type`, in any case with `ignore_case`
+fn type_matches(chunk: &Chunk, chunk_type: &str, ignore_case: bool) -> bool {
+    let Ok(bytes) = <[u8; 4]>::try_from(chunk_type.as_bytes()) else {
+        return false;
+    };
+    let chunk_type = ChunkType::from_bytes_unchecked(bytes);
+    if ignore_case {
+        chunk.chunk_type().eq_ignore_ascii_case(&chunk_type)
+    } else {
+        chunk.chunk_type() == &chunk_type
+    }
+}
+
+/// Lists the distinct types of the chunks of `png` matching `chunk_type`, see
+/// `type_matches`, in file order. That is only `chunk_type` itself without
+/// `ignore_case` or when no chunk matches, so lookups report it as missing.
+fn matching_types(png: &Png, chunk_type: &str, ignore_case: bool) -> Vec<String> {
+    let mut types: Vec<String> = Vec::new();
+    if ignore_case {
+        for chunk in png.iter() {
+            let found = chunk.chunk_type().to_string();
+            if type_matches(chunk, chunk_type, true) && !types.contains(&found) {
+                types.push(found);
+            }
+        }
+    }
+    if types.is_empty() {
+        types.push(chunk_type.to_string());
+    }
+    types
+}
+
+/// Removes a chunk from a PNG file and saves the result.
+/// With `--in-place` a symlink is followed and its target is overwritten.
+pub fn remove(args: RemoveArgs, verbosity: Verbosity) -> Result<RemoveOutcome> {
+    let write = !args.size_delta && !args.dry_run;
+    if write {
+        let has_output = args.output.is_some() || args.output_template.is_some();
+        check_destination(&args.file, has_output, args.in_place)?;
+    }
+
+    let mut png: Png = load_png(&args.file)?;
+    let original_len = png.to_file_bytes()?.len();
+    let mut removed: Vec<(String, usize)> = Vec::new();
+    for query in args.chunk_types.iter() {
+        let mut types = matching_types(&png, query, args.ignore_case);
+        if !args.all {
+            types.truncate(1);
+        }
+        for chunk_type in types {
+            if !args.force && ChunkType::from_str(&chunk_type).is_ok_and(|ct| ct.is_critical()) {
+                return Err(Box::new(CommandError::CriticalChunk(chunk_type)));
+            }
+            let details = detail_lines(&png, Some(&chunk_type), None, verbosity);
+            let shown = if args.all { details.len() } else { 1 };
+            for line in details.iter().take(shown) {
+                println!("{}", line);
+            }
+            let count = if args.all {
+                png.remove_all_chunks(&chunk_type).len()
+            } else {
+                png.remove_chunk(&chunk_type).map(|_| 1)?
+            };
+            removed.push((chunk_type, count));
+        }
+    }
+
+    let output = output_path(
+        &args.file,
+        args.output.as_deref(),
+        args.output_template.as_deref(),
+    );
+    let bytes = png.to_file_bytes()?;
+    if write {
+        write_file(&output, &bytes)?;
+        if let Some(ref log) = args.log {
+            audit::record(log, "remove", &output, &args.chunk_types.join(","))?;
+        }
+    }
+    Ok(RemoveOutcome {
+        removed,
+        size_delta: size_delta(original_len, bytes.len()),
+        written: write,
+    })
+}
+
+/// Replaces the data of the first chunk with the given type by a new message,
+/// keeping the chunk where it is
+pub fn replace(args: ReplaceArgs) -> Result<ReplaceOutcome> {
+    check_destination(&args.file, args.output.is_some(), args.in_place)?;
+
+    let mut png: Png = load_png(&args.file)?;
+    let data = match args.key {
+        Some(ref key) => cipher::xor_encode(args.message.as_bytes(), key),
+        None => args.message.into_bytes(),
+    };
+    png.replace_chunk_data(&args.chunk_type, data)?;
+
+    let output = args.output.unwrap_or(args.file);
+    write_file(&output, &png.to_file_bytes()?)?;
+    Ok(ReplaceOutcome {
+        chunk_type: args.chunk_type,
+        output,
+    })
+}
+
+/// Prints all of the chunks in a PNG file
+pub fn print_chunks(args: PrintArgs) -> Result<PrintOutcome> {
+    Ok(PrintOutcome {
+        pngs: printed_pngs(&args)?,
+        types_only: args.types_only,
+        json: args.json,
+        checksum: args.checksum,
+    })
+}
+
+/// Writes the chunks `print` reads to `out` with `--jsonl`, see `write_jsonl`. They
+/// are streamed so memory stays constant, except with `--multi` and
+/// `--no-validate-type` which need the whole file.
+pub fn print_jsonl<W: Write>(args: PrintArgs, mut out: W) -> Result<()> {
+    if args.multi || args.no_validate_type {
+        for png in printed_pngs(&args)? {
+            write_jsonl(png.chunks().iter().cloned().map(Ok), &mut out)?;
+        }
+        return Ok(());
+    }
+    let chunks = match args.since_offset {
+        Some(offset) => Png::stream_chunks_at(open_input(&args.file)?, offset)?,
+        None => Png::stream_chunks(open_input_at(&args.file, args.offset)?)?,
+    };
+    write_jsonl(chunks, out)
+}
+
+/// Reads the PNGs `print` shows: one from `--since-offset` or `--offset`, or every
+/// one concatenated in the file with `--multi`
+fn printed_pngs(args: &PrintArgs) -> Result<Vec<Png>> {
+    Ok(match args.since_offset {
+        Some(offset) => vec![Png::from_reader_at(open_input(&args.file)?, offset)?],
+        None if args.multi => Png::from_bytes_multi(&read_input(&args.file)?)?,
+        None if args.offset.is_some() => vec![load_png_at(&args.file, args.offset)?],
+        None => vec![read_png(&args.file, args.no_validate_type)?],
+    })
+}
+
+/// Writes one JSON object per chunk and line to `out` as the chunks are read,
+/// flushing after each line so a reader downstream gets them as they come. Fails
+/// once they are all written if the last one isn't IEND.
+fn write_jsonl<W: Write>(
+    chunks: impl IntoIterator<Item = pngme::Result<Chunk>>,
+    mut out: W,
+) -> Result<()> {
+    let mut ended = false;
+    for chunk in chunks {
+        let chunk = chunk?;
+        serde_json::to_writer(&mut out, &chunk)?;
+        writeln!(out)?;
+        out.flush()?;
+        ended = chunk.chunk_type().bytes() == *b"IEND";
+    }
+    if !ended {
+        return Err(Box::new(PngError::MissingIend));
+    }
+    Ok(())
+}
+
+/// Lists the type of every chunk of `png` in file order
+fn chunk_types(png: &Png) -> Vec<String> {
+    png.iter()
+        .map(|chunk| chunk.chunk_type().to_string())
+        .collect()
+}
+
+/// Writes each PNG file concatenated in a file to its own file
+pub fn split(args: SplitArgs) -> Result<SplitOutcome> {
+    let pngs = Png::from_bytes_multi(&read_input(&args.file)?)?;
+    let mut files: Vec<PathBuf> = Vec::with_capacity(pngs.len());
+    for (index, png) in pngs.iter().enumerate() {
+        let template = args.output_template.replace("{index}", &index.to_string());
+        let file = expand_output_template(&template, &args.file);
+        write_file(&file, &png.as_bytes())?;
+        files.push(file);
+    }
+    Ok(SplitOutcome { files })
+}
+
+/// Removes duplicated ancillary chunks from a PNG file and saves the result
+pub fn dedup(args: DedupArgs) -> Result<DedupOutcome> {
+    check_destination(&args.file, args.output.is_some(), args.in_place)?;
+    let mut png: Png = load_png(&args.file)?;
+    let removed = png.dedup(args.keep_last);
+
+    let output = args.output.unwrap_or(args.file);
+    write_file(&output, &png.to_file_bytes()?)?;
+    if let Some(log) = args.log {
+        let detail = format!("{} removed", removed);
+        audit::record(&log, "dedup", &output, &detail)?;
+    }
+    Ok(DedupOutcome { removed })
+}
+
+/// Extracts the raw TIFF/EXIF bytes of the eXIf chunk in a PNG file
+pub fn exif(args: ExifArgs) -> Result<ExifOutcome> {
+    let png: Png = load_png_at(&args.file, args.offset)?;
+    let chunk = png
+        .chunk_by_type("eXIf")
+        .ok_or(PngError::UnknownChunkType("eXIf".to_string()))?;
+    let data = chunk.data();
+
+    match args.output {
+        Some(file) => {
+            fs::write(&file, data)?;
+            Ok(ExifOutcome::Written(file))
+        }
+        None => {
+            let byte_order = match data.get(..2) {
+                Some(b"II") => "little-endian",
+                Some(b"MM") => "big-endian",
+                _ => "unknown byte order",
+            };
+            Ok(ExifOutcome::Summary {
+                bytes: data.len(),
+                byte_order,
+            })
+        }
+    }
+}
+
+/// Writes the data of the first chunk with the given type, or the one at `--index`
+/// among them, to a file
+pub fn extract(args: ExtractArgs, verbosity: Verbosity) -> Result<ExtractOutcome> {
+    let png: Png = load_png_at(&args.file, args.offset)?;
+    let chunks = png.chunks_by_type(&args.chunk_type);
+    if chunks.is_empty() {
+        return Err(Box::new(PngError::UnknownChunkType(args.chunk_type)));
+    }
+    let index = args.index.unwrap_or(0);
+    let chunk = chunks
+        .get(index)
+        .ok_or(PngError::IndexOutOfRange(index, chunks.len()))?;
+
+    print_details(&png, Some(&args.chunk_type), args.offset, verbosity);
+    fs::write(&args.output, format_data(chunk.data(), args.format))?;
+    Ok(ExtractOutcome {
+        found: chunks.len(),
+        chunk_type: args.chunk_type,
+        output: args.output,
+        index: args.index,
+    })
+}
+
+/// Encodes `data` in the given `DataFormat`
+fn format_data(data: &[u8], format: DataFormat) -> Vec<u8> {
+    match format {
+        DataFormat::Raw => data.to_vec(),
+        DataFormat::Base64 => base64::engine::general_purpose::STANDARD
+            .encode(data)
+            .into_bytes(),
+        DataFormat::Hex => to_hex(data).into_bytes(),
+    }
+}
+
+/// Prints a hash over the IHDR and IDAT chunks of a PNG file, so the fingerprint
+/// only changes when the image itself does
+pub fn fingerprint(args: FingerprintArgs) -> Result<FingerprintOutcome> {
+    let png: Png = load_png_at(&args.file, args.offset)?;
+    Ok(FingerprintOutcome {
+        digest: image_fingerprint(&png, args.hash)?,
+    })
+}
+
+/// Hashes the type and data of every IHDR and IDAT chunk in order
+fn image_fingerprint(png: &Png, algorithm: HashAlgorithm) -> Result<Vec<u8>> {
+    let image: Vec<u8> = png
+        .chunks()
+        .iter()
+        .filter(|chunk| matches!(&chunk.chunk_type().bytes(), b"IHDR" | b"IDAT"))
+        .flat_map(|chunk| {
+            chunk
+                .chunk_type()
+                .bytes()
+                .into_iter()
+                .chain(chunk.data().iter().copied())
+        })
+        .collect();
+
+    match algorithm {
+        HashAlgorithm::Sha256 => {
+            use sha2::Digest;
+            Ok(sha2::Sha256::digest(&image).to_vec())
+        }
+        #[cfg(feature = "sha1")]
+        HashAlgorithm::Sha1 => {
+            use sha1::Digest;
+            Ok(sha1::Sha1::digest(&image).to_vec())
+        }
+        #[cfg(not(feature = "sha1"))]
+        HashAlgorithm::Sha1 => Err(Box::new(CommandError::MissingFeature("sha1"))),
+        #[cfg(feature = "blake3")]
+        HashAlgorithm::Blake3 => Ok(blake3::hash(&image).as_bytes().to_vec()),
+        #[cfg(not(feature = "blake3"))]
+        HashAlgorithm::Blake3 => Err(Box::new(CommandError::MissingFeature("blake3"))),
+    }
+}
+
+/// Formats `data` as lowercase hexadecimal
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Parses a PNG file, which checks its header, every chunk CRC and that it ends with
+/// IEND, then checks that nothing follows IEND and the requested invariants
+pub fn validate(args: ValidateArgs, verbosity: Verbosity) -> Result<ValidateOutcome> {
+    if args.verify_signature_only {
+        let mut header: Vec<u8> = Vec::with_capacity(Png::HEADER_LENGHT);
+        open_input_at(&args.file, args.offset)?
+            .take(Png::HEADER_LENGHT as u64)
+            .read_to_end(&mut header)?;
+        Png::check_signature(&header)?;
+        return Ok(ValidateOutcome {
+            file: args.file,
+            signature_only: true,
+        });
+    }
+    if args.collect_errors {
+        let bytes = read_input(&args.file)?;
+        let problems = validation_problems(bytes_from(&bytes, args.offset)?, &args)?;
+        if !problems.is_empty() {
+            return Err(Box::new(CommandError::ValidationFailed(problems)));
+        }
+        return Ok(ValidateOutcome {
+            file: args.file,
+            signature_only: false,
+        });
+    }
+
+    let png: Png = load_png_at(&args.file, args.offset)?;
+    print_details(&png, None, args.offset, verbosity);
+    png.check_trailer()?;
+
+    for chunk_type in args.require_unique.iter() {
+        let count = png.count_by_type(chunk_type);
+        if count != 1 {
+            return Err(Box::new(CommandError::NotUnique(chunk_type.clone(), count)));
+        }
+    }
+    if args.strict_order {
+        png.check_order()?;
+    }
+    Ok(ValidateOutcome {
+        file: args.file,
+        signature_only: false,
+    })
+}
+
+/// Runs every check `validate` was asked for on the bytes of a PNG file and lists the
+/// problems found. Only a file whose chunks can't be parsed at all fails.
+fn validation_problems(bytes: &[u8], args: &ValidateArgs) -> Result<Vec<String>> {
+    let (mut png, crc_errors) = Png::from_bytes_lenient(bytes)?;
+    if args.offset.is_some() {
+        png.strip_trailing_bytes();
+    }
+    let mut problems: Vec<String> = crc_errors
+        .iter()
+        .map(|error| {
+            format!(
+                "Chunk {}: stored CRC {:08x}, computed {:08x}",
+                error.index, error.stored, error.computed
+            )
+        })
+        .collect();
+    for check in [png.check_iend(), png.check_trailer()] {
+        if let Err(err) = check {
+            problems.push(err.to_string());
+        }
+    }
+    for chunk_type in args.require_unique.iter() {
+        let count = png.count_by_type(chunk_type);
+        if count != 1 {
+            problems.push(CommandError::NotUnique(chunk_type.clone(), count).to_string());
+        }
+    }
+    if args.strict_order {
+        if let Err(err) = png.check_order() {
+            problems.push(err.to_string());
+        }
+    }
+    Ok(problems)
+}
+
+/// Renames the custom ancillary chunks of a PNG file under a prefix and saves the result
+pub fn namespace(args: NamespaceArgs) -> Result<NamespaceOutcome> {
+    check_destination(&args.file, args.output.is_some(), args.in_place)?;
+    let mut png: Png = load_png(&args.file)?;
+    let renamed = png.apply_namespace(&args.prefix)?;
+
+    write_file(
+        args.output.as_ref().unwrap_or(&args.file),
+        &png.to_file_bytes()?,
+    )?;
+    Ok(NamespaceOutcome {
+        renamed,
+        prefix: args.prefix,
+    })
+}
+
+/// Scores how likely it is that a PNG file carries chunks added by pngme
+pub fn detect(args: DetectArgs) -> Result<DetectOutcome> {
+    let png: Png = load_png_at(&args.file, args.offset)?;
+    Ok(DetectOutcome {
+        score: pngme_score(&png),
+        file: args.file,
+    })
+}
+
+/// Prints a histogram of the chunk types of a PNG file with their data size in bytes
+pub fn count(args: CountArgs) -> Result<CountOutcome> {
+    let png: Png = load_png_at(&args.file, args.offset)?;
+    let mut rows = chunk_histogram(&png);
+    if let Some(key) = args.sort_by {
+        sort_histogram(&mut rows, key, args.desc);
+    }
+    Ok(CountOutcome {
+        total: args.summary.then(|| histogram_total(&rows)),
+        rows,
+    })
+}
+
+/// Prints the size of the data appended after IEND, removing it when asked
+pub fn trailer(args: TrailerArgs) -> Result<TrailerOutcome> {
+    if args.strip {
+        check_destination(&args.file, args.output.is_some(), args.in_place)?;
+    }
+    let mut png: Png = match args.offset {
+        Some(_) => {
+            Png::from_bytes_with_trailer(bytes_from(&read_input(&args.file)?, args.offset)?)?
+        }
+        None => load_png_with(&args.file, Png::from_bytes_with_trailer)?,
+    };
+    if !args.strip {
+        return Ok(TrailerOutcome::Found {
+            bytes: png.trailing_bytes().len(),
+            file: args.file,
+        });
+    }
+
+    let stripped = png.strip_trailing_bytes();
+    write_file(
+        args.output.as_ref().unwrap_or(&args.file),
+        &png.to_file_bytes()?,
+    )?;
+    Ok(TrailerOutcome::Stripped(stripped.len()))
+}
+
+/// Prints the image dimensions of a PNG file followed by a count of each chunk type
+pub fn info(args: InfoArgs) -> Result<InfoOutcome> {
+    let png: Png = load_png_at(&args.file, args.offset)?;
+    Ok(InfoOutcome {
+        header: png.image_header()?,
+        palette: png.palette_histogram()?,
+        chunks: chunk_histogram(&png)
+            .into_iter()
+            .map(|(chunk_type, count, _)| (chunk_type, count))
+            .collect(),
+    })
+}
+
+/// Prints a table of the chunks of a PNG file, optionally only those of one type
+pub fn list(args: ListArgs) -> Result<ListOutcome> {
+    let png: Png = match args.offset {
+        Some(offset) => Png::from_bytes_embedded(&read_input(&args.file)?, offset)?,
+        None => read_png(&args.file, args.no_validate_type)?,
+    };
+    if args.json {
+        return Ok(ListOutcome::Json(list_json(
+            &png,
+            args.chunk_type.as_deref(),
+        )?));
+    }
+    Ok(ListOutcome::Table(list_lines(
+        &png,
+        args.chunk_type.as_deref(),
+    )))
+}
+
+/// Rewrites a PNG file with the correct CRC for every chunk, such as one whose
+/// chunks were edited by hand. A truncated file keeps its complete chunks and
+/// gets IEND back. A gzip-compressed file stays compressed.
+pub fn repair(args: RepairArgs, verbosity: Verbosity) -> Result<RepairOutcome> {
+    check_destination(&args.file, args.output.is_some(), args.in_place)?;
+    let (mut crc_errors, mut dropped, mut changed) = (Vec::new(), 0, false);
+    let png = load_png_with(&args.file, |bytes| {
+        let (png, errors, truncated) = Png::from_bytes_salvaged(bytes)?;
+        (crc_errors, dropped, changed) = (errors, truncated, png.as_bytes() != bytes);
+        Ok(png)
+    })?;
+    match args.output {
+        Some(ref output) => write_file(output, &png.to_file_bytes()?)?,
+        None if changed => write_file(&args.file, &png.to_file_bytes()?)?,
+        None => {}
+    }
+    print_details(&png, None, None, verbosity);
+    Ok(RepairOutcome {
+        crc_errors,
+        dropped,
+        report: args.report || verbosity == Verbosity::Verbose,
+    })
+}
+
+/// Lists the chunks of `png` with their index, only those of `chunk_type` if given
+fn filter_chunks<'a>(
+    png: &'a Png,
+    chunk_type: Option<&'a str>,
+) -> impl Iterator<Item = (usize, &'a Chunk)> {
+    png.chunks()
+        .iter()
+        .enumerate()
+        .filter(move |(_, chunk)| chunk_type.is_none_or(|ct| chunk.chunk_type().to_string() == ct))
+}
+
+/// Serializes the listed chunks as a JSON array
+fn list_json(png: &Png, chunk_type: Option<&str>) -> Result<String> {
+    let chunks: Vec<&Chunk> = filter_chunks(png, chunk_type)
+        .map(|(_, chunk)| chunk)
+        .collect();
+    Ok(serde_json::to_string_pretty(&chunks)?)
+}
+
+/// Formats one line per chunk with its index, type, length, CRC and flags.
+/// Flags are critical, public, reserved bit valid and safe to copy, `-` when unset.
+fn list_lines(png: &Png, chunk_type: Option<&str>) -> Vec<String> {
+    filter_chunks(png, chunk_type)
+        .map(|(index, chunk)| {
+            let ct = chunk.chunk_type();
+            let flag = |set: bool, c: char| if set { c } else { '-' };
+            let flags: String = [
+                flag(ct.is_critical(), 'C'),
+                flag(ct.is_public(), 'P'),
+                flag(ct.is_reserved_bit_valid(), 'R'),
+                flag(ct.is_safe_to_copy(), 'S'),
+            ]
+            .iter()
+            .collect();
+            format!(
+                "{:>5}  {}  {:>10}  {:08x}  {}",
+                index,
+                ct,
+                chunk.length(),
+                chunk.crc(),
+                flags
+            )
+        })
+        .collect()
+}
+
+/// Groups the chunks of `png` by type, in order of first appearance, as
+/// `(chunk_type, count, data bytes)` rows
+fn chunk_histogram(png: &Png) -> Vec<(String, usize, usize)> {
+    let mut rows: Vec<(String, usize, usize)> = Vec::new();
+    for chunk in png.chunks() {
+        let chunk_type = chunk.chunk_type().to_string();
+        match rows
+            .iter_mut()
+            .find(|(row_type, _, _)| *row_type == chunk_type)
+        {
+            Some(row) => {
+                row.1 += 1;
+                row.2 += chunk.data().len();
+            }
+            None => rows.push((chunk_type, 1, chunk.data().len())),
+        }
+    }
+    rows
+}
+
+/// Sorts histogram rows by the column given by `key`, ties keep their order
+fn sort_histogram(rows: &mut [(String, usize, usize)], key: SortKey, desc: bool) {
+    rows.sort_by(|a, b| {
+        let order = match key {
+            SortKey::Type => a.0.cmp(&b.0),
+            SortKey::Count => a.1.cmp(&b.1),
+            SortKey::Size => a.2.cmp(&b.2),
+        };
+        if desc {
+            order.reverse()
+        } else {
+            order
+        }
+    });
+}
+
+/// Sums the chunk count and data bytes of every histogram row
+fn histogram_total(rows: &[(String, usize, usize)]) -> (usize, usize) {
+    rows.iter()
+        .fold((0, 0), |(count, bytes), row| (count + row.1, bytes + row.2))
+}
+
+/// Scores from 0 to 100 the signs of pngme usage: chunks with non-standard types,
+/// placed right before or after IEND, holding readable text
+fn pngme_score(png: &Png) -> u8 {
+    let chunks = png.chunks();
+    let custom: Vec<usize> = chunks
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| !Png::is_standard_type(&chunk.chunk_type().to_string()))
+        .map(|(index, _)| index)
+        .collect();
+    let Some(&last) = custom.last() else {
+        return 0;
+    };
+
+    let mut score = 50;
+    let iend = chunks
+        .iter()
+        .position(|chunk| chunk.chunk_type().bytes() == *b"IEND");
+    if iend.is_none_or(|iend| last + 1 >= iend) {
+        score += 30;
+    }
+    if custom
+        .iter()
+        .any(|&index| chunks[index].data_as_string().is_ok())
+    {
+        score += 20;
+    }
+    score
+}
+
+/// Prints how many custom ancillary chunks a PNG file has, the bytes they hold and
+/// how much data a single chunk could hold
+pub fn capacity(args: CapacityArgs, verbosity: Verbosity) -> Result<CapacityOutcome> {
+    let png: Png = load_png(&args.file)?;
+    print_details(&png, None, None, verbosity);
+    Ok(CapacityOutcome {
+        capacity: png.total_capacity(),
+    })
+}
+
+/// Prints the chunks added, removed or changed between two PNG files
+pub fn diff(args: DiffArgs, verbosity: Verbosity) -> Result<DiffOutcome> {
+    if is_stdin(&args.before) && is_stdin(&args.after) {
+        return Err(Box::new(CommandError::StdinTwice));
+    }
+    let (before, after) = (load_png(&args.before)?, load_png(&args.after)?);
+    for (file, png) in [(&args.before, &before), (&args.after, &after)] {
+        if verbosity == Verbosity::Verbose {
+            println!("'{}':", file.display());
+        }
+        print_details(png, None, None, verbosity);
+    }
+    Ok(DiffOutcome {
+        lines: diff_lines(&before, &after),
+        before: args.before,
+        after: args.after,
+    })
+}
+
+/// Compares the chunks of two PNGs by type and CRC. Chunks only in `after` are
+/// reported with `+`, only in `before` with `-`, and a chunk of `before` whose type
+/// is found in `after` with other data with `~`.
+fn diff_lines(before: &Png, after: &Png) -> Vec<String> {
+    let same = |a: &Chunk, b: &Chunk| a.chunk_type() == b.chunk_type() && a.crc() == b.crc();
+    let mut removed: Vec<&Chunk> = before.chunks().iter().collect();
+    let mut added: Vec<&Chunk> = Vec::new();
+    for chunk in after.chunks() {
+        match removed.iter().position(|old| same(old, chunk)) {
+            Some(index) => {
+                removed.remove(index);
+            }
+            None => added.push(chunk),
+        }
+    }
+
+    let describe = |chunk: &Chunk| {
+        format!(
+            "{} ({} bytes, CRC {:08x})",
+            chunk.chunk_type(),
+            chunk.length(),
+            chunk.crc()
+        )
+    };
+    let mut lines: Vec<String> = Vec::new();
+    for old in removed {
+        match added
+            .iter()
+            .position(|new| new.chunk_type() == old.chunk_type())
+        {
+            Some(index) => {
+                let new = added.remove(index);
+                lines.push(format!(
+                    "~ {} data differs: {} -> {} bytes, CRC {:08x} -> {:08x}",
+                    old.chunk_type(),
+                    old.length(),
+                    new.length(),
+                    old.crc(),
+                    new.crc()
+                ));
+            }
+            None => lines.push(format!("- {}", describe(old))),
+        }
+    }
+    lines.extend(added.into_iter().map(|new| format!("+ {}", describe(new))));
+    lines
+}
+
+/// Returns the completion script of a shell, see `completion_script`
+pub fn completions(args: CompletionsArgs) -> Vec<u8> {
+    completion_script(args.shell)
+}
+
+/// Generates the completion script of `shell` for every pngme command. It is built
+/// in memory first because `clap_complete` panics when a write fails, e.g. on a
+/// closed pipe.
+fn completion_script(shell: clap_complete::Shell) -> Vec<u8> {
+    let mut script: Vec<u8> = Vec::new();
+    clap_complete::generate(shell, &mut Cli::command(), "pngme", &mut script);
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::io::Cursor;
+    use std::num::NonZeroUsize;
+
+    fn testing_png() -> Png {
+        #[rustfmt::skip]
+        let ihdr: Vec<u8> = vec![
+            0, 0, 0, 1, // width
+            0, 0, 0, 1, // height
+            8, 6, 0, 0, 0, // bit depth, color type, compression, filter, interlace
+        ];
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), ihdr),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![120, 1, 1, 0, 0]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ])
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("pngme_{}_{}", std::process::id(), name))
+    }
+
+    fn encode_args(file: &Path, message: &str) -> EncodeArgs {
+        EncodeArgs {
+            file: file.to_path_buf(),
+            chunk_type: "ruSt".to_string(),
+            message: Some(message.to_string()),
+            message_file: None,
+            message_env: None,
+            output: None,
+            output_template: None,
+            key: None,
+            cipher: None,
+            key_stdin: false,
+            allow_empty: false,
+            at_offset: None,
+            spread: false,
+            parts: None,
+            compress: false,
+            base64: false,
+            max_growth: None,
+            apng: false,
+            at: None,
+            no_duplicate: false,
+            in_place: true,
+            ztxt: false,
+            text: false,
+            keyword: None,
+            append_to_existing: false,
+            size_delta: false,
+            dry_run: false,
+            log: None,
+        }
+    }
+
+    fn decode_args(file: &Path, chunk_type: &str) -> DecodeArgs {
+        DecodeArgs {
+            file: file.to_path_buf(),
+            offset: None,
+            chunk_type: Some(chunk_type.to_string()),
+            key: None,
+            key_stdin: false,
+            cipher: None,
+            output: None,
+            spread: false,
+            compress: false,
+            ztxt: false,
+            text: false,
+            base64: false,
+            all: false,
+            grep: None,
+            encoding: TextEncoding::Utf8,
+            json: false,
+            try_all_with_key: None,
+            ignore_case: false,
+        }
+    }
+
+    #[test]
+    fn test_decode_all_base64() {
+        let file = temp_path("decode_all_base64.png");
+        let mut png = testing_png();
+        for message in [&[0xff, 0x00][..], b"hi"] {
+            png.append_chunk(Chunk::new(
+                ChunkType::from_str("ruSt").unwrap(),
+                message.to_vec(),
+            ));
+        }
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = DecodeArgs {
+            all: true,
+            base64: true,
+            ..decode_args(&file, "ruSt")
+        };
+        assert_eq!(
+            decode(args, Verbosity::Normal).unwrap(),
+            DecodeOutcome::Messages {
+                keyword: None,
+                messages: vec!["/wA=".to_string(), "aGk=".to_string()],
+            }
+        );
+
+        for flag in ["--json", "--spread", "--text", "--ztxt"] {
+            let args = ["pngme", "decode", "f.png", "ruSt", "--all", flag];
+            assert!(Cli::try_parse_from(args).is_err(), "{}", flag);
+        }
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_decode_all_ignore_case() {
+        let file = temp_path("decode_all_ignore_case.png");
+        let mut png = testing_png();
+        for (chunk_type, message) in [("rUST", "one"), ("ruSt", "two"), ("rUST", "three")] {
+            png.append_chunk(Chunk::from_type_and_str(chunk_type, message).unwrap());
+        }
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = DecodeArgs {
+            all: true,
+            ignore_case: true,
+            ..decode_args(&file, "rust")
+        };
+        assert_eq!(
+            decode(args, Verbosity::Normal).unwrap(),
+            DecodeOutcome::Messages {
+                keyword: None,
+                messages: vec!["one".to_string(), "two".to_string(), "three".to_string()],
+            }
+        );
+
+        let args = DecodeArgs {
+            all: true,
+            ..decode_args(&file, "rust")
+        };
+        assert_eq!(
+            decode(args, Verbosity::Normal).unwrap(),
+            DecodeOutcome::NotFound("rust".to_string())
+        );
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_apng() {
+        let file = temp_path("encode_apng.png");
+        let png = testing_png();
+        let chunk = |chunk_type: &str, data: Vec<u8>| {
+            Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+        };
+        let chunks = vec![
+            png.chunks()[0].clone(),
+            chunk("acTL", vec![0, 0, 0, 2, 0, 0, 0, 0]),
+            chunk("fcTL", vec![0; 26]),
+            png.chunks()[1].clone(),
+            chunk("fcTL", vec![0; 26]),
+            chunk("fdAT", vec![0, 0, 0, 2, 120, 1, 1, 0, 0]),
+            png.chunks()[2].clone(),
+        ];
+        let apng = Png::from_chunks(chunks);
+        fs::write(&file, apng.as_bytes()).unwrap();
+
+        let args = EncodeArgs {
+            apng: true,
+            ..encode_args(&file, "hidden")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(
+            chunk_types(&png),
+            ["IHDR", "ruSt", "acTL", "fcTL", "IDAT", "fcTL", "fdAT", "IEND"]
+        );
+        assert!(validate(validate_args(&file), Verbosity::Normal).is_ok());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_max_growth() {
+        let file = temp_path("encode_max_growth.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+
+        let args = EncodeArgs {
+            max_growth: Some(10.0),
+            ..encode_args(&file, &"a".repeat(1000))
+        };
+        let err = encode(args, Verbosity::Normal).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::GrowthExceeded(_, _))
+        ));
+        assert_eq!(fs::read(&file).unwrap(), testing_png().as_bytes());
+
+        let args = EncodeArgs {
+            max_growth: Some(100.0),
+            ..encode_args(&file, "a")
+        };
+        assert!(encode(args, Verbosity::Normal).is_ok());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_at_index() {
+        let file = temp_path("encode_at.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+
+        let args = EncodeArgs {
+            at: Some(1),
+            ..encode_args(&file, "before IDAT")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "ruSt", "IDAT", "IEND"]);
+
+        let args = EncodeArgs {
+            at: Some(9),
+            ..encode_args(&file, "out of range")
+        };
+        assert!(encode(args, Verbosity::Normal).is_err());
+
+        // Neither before IHDR nor past IEND
+        for at in [0, 4] {
+            let args = EncodeArgs {
+                at: Some(at),
+                ..encode_args(&file, "misplaced")
+            };
+            assert!(encode(args, Verbosity::Normal).is_err());
+        }
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "ruSt", "IDAT", "IEND"]);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let file = temp_path("ignore_case.png");
+        let output = temp_path("ignore_case.txt");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        let args = EncodeArgs {
+            chunk_type: "rUST".to_string(),
+            ..encode_args(&file, "hidden")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+
+        let decode_args = |ignore_case: bool| DecodeArgs {
+            file: file.clone(),
+            chunk_type: Some("rust".to_string()),
+            key: None,
+            output: Some(output.clone()),
+            spread: false,
+            compress: false,
+            base64: false,
+            all: false,
+            grep: None,
+            encoding: TextEncoding::Utf8,
+            cipher: None,
+            key_stdin: false,
+            offset: None,
+            try_all_with_key: None,
+            ignore_case,
+            ztxt: false,
+            text: false,
+            json: false,
+        };
+        decode(decode_args(false), Verbosity::Normal).unwrap();
+        assert!(!output.exists());
+        decode(decode_args(true), Verbosity::Normal).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), b"hidden");
+
+        let png = Png::from_file(&file).unwrap();
+        let chunk = png.chunk_by_type("rUST").unwrap();
+        assert!(!type_matches(chunk, "rust", false));
+        assert!(type_matches(chunk, "rust", true));
+
+        assert!(remove(remove_args(&file, &["rust"]), Verbosity::Normal).is_err());
+        let args = RemoveArgs {
+            ignore_case: true,
+            ..remove_args(&file, &["rust"])
+        };
+        let outcome = remove(args, Verbosity::Normal).unwrap();
+        assert_eq!(outcome.removed, vec![("rUST".to_string(), 1)]);
+
+        let args = RemoveArgs {
+            ignore_case: true,
+            ..remove_args(&file, &["iend"])
+        };
+        assert!(remove(args, Verbosity::Normal).is_err());
+        fs::remove_file(file).unwrap();
+        fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_encode_no_duplicate() {
+        let file = temp_path("encode_no_duplicate.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        let no_duplicate = |message: &str| EncodeArgs {
+            no_duplicate: true,
+            ..encode_args(&file, message)
+        };
+
+        assert!(
+            !encode(no_duplicate("first"), Verbosity::Normal)
+                .unwrap()
+                .duplicate
+        );
+        let err = encode(no_duplicate("second"), Verbosity::Normal).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::DuplicateChunk(_))
+        ));
+
+        let args = EncodeArgs {
+            append_to_existing: true,
+            ..no_duplicate(" second")
+        };
+        let outcome = encode(args, Verbosity::Normal).unwrap();
+        assert_eq!((outcome.chunks_added, outcome.appended), (0, true));
+        assert_eq!(outcome.to_string(), "Message appended to Chunk 'ruSt'");
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(png.count_by_type("ruSt"), 1);
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"first second");
+
+        for flag in [
+            "--key=k",
+            "--key-stdin",
+            "--cipher=xor",
+            "--compress",
+            "--text",
+        ] {
+            let args = [
+                "pngme",
+                "encode",
+                "f.png",
+                "ruSt",
+                "msg",
+                "--append-to-existing",
+                flag,
+            ];
+            assert!(Cli::try_parse_from(args).is_err(), "{}", flag);
+        }
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_duplicate_warns() {
+        let file = temp_path("encode_duplicate_warns.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+
+        assert!(
+            !encode(encode_args(&file, "first"), Verbosity::Normal)
+                .unwrap()
+                .duplicate
+        );
+        let outcome = encode(encode_args(&file, "second"), Verbosity::Normal).unwrap();
+        assert!(outcome.duplicate);
+        assert!(outcome.warning().unwrap().contains("Chunk 'ruSt'"));
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(png.count_by_type("ruSt"), 2);
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"first");
+
+        // Splicing at a byte offset checks for duplicates too
+        let offset = Png::HEADER_LENGHT + png.chunks()[0].as_bytes().len();
+        let at_offset = |no_duplicate| EncodeArgs {
+            at_offset: Some(offset),
+            no_duplicate,
+            ..encode_args(&file, "third")
+        };
+        let err = encode(at_offset(true), Verbosity::Normal).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::DuplicateChunk(_))
+        ));
+        assert!(
+            encode(at_offset(false), Verbosity::Normal)
+                .unwrap()
+                .duplicate
+        );
+        assert_eq!(Png::from_file(&file).unwrap().count_by_type("ruSt"), 3);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_ztxt_round_trip() {
+        let file = temp_path("encode_ztxt.png");
+        let output = temp_path("encode_ztxt.txt");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+
+        let args = EncodeArgs {
+            chunk_type: "zTXt".to_string(),
+            ztxt: true,
+            keyword: Some("Comment".to_string()),
+            ..encode_args(&file, "hidden text")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        let data = png.chunk_by_type("zTXt").unwrap().data();
+        assert!(data.starts_with(b"Comment\0\0"));
+        let mut text = String::new();
+        ZlibDecoder::new(&data[9..])
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "hidden text");
+
+        let args = DecodeArgs {
+            file: file.clone(),
+            chunk_type: Some("zTXt".to_string()),
+            key: None,
+            output: Some(output.clone()),
+            spread: false,
+            compress: false,
+            base64: false,
+            all: false,
+            grep: None,
+            encoding: TextEncoding::Utf8,
+            cipher: None,
+            key_stdin: false,
+            offset: None,
+            try_all_with_key: None,
+            ignore_case: false,
+            ztxt: true,
+            text: false,
+            json: false,
+        };
+        decode(args, Verbosity::Normal).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), b"hidden text");
+        assert_eq!(
+            parse_ztxt(data).unwrap(),
+            ("Comment".to_string(), b"hidden text".to_vec())
+        );
+        fs::remove_file(file).unwrap();
+        fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_encode_text_layout() {
+        let file = temp_path("encode_text.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+
+        let args = EncodeArgs {
+            chunk_type: "tEXt".to_string(),
+            text: true,
+            keyword: Some("Copyright".to_string()),
+            ..encode_args(&file, "pngme authors")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        let data = png.chunk_by_type("tEXt").unwrap().data();
+        assert_eq!(data, b"Copyright\0pngme authors");
+        let (keyword, text) = split_keyword(data).unwrap();
+        assert_eq!(keyword, "Copyright");
+        assert_eq!(text, b"pngme authors");
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_text_keyword_validation() {
+        assert_eq!(text_data("Caf\u{e9}", b"x").unwrap(), b"Caf\xe9\0x");
+        let (keyword, _) = split_keyword(b"Caf\xe9\0x").unwrap();
+        assert_eq!(keyword, "Caf\u{e9}");
+
+        let invalid = [
+            "",
+            "\u{20ac}uro",
+            "a\0b",
+            &"k".repeat(80),
+            " Comment",
+            "Comment ",
+            "Author  name",
+            "Tab\tbed",
+            "no\u{a0}break",
+        ];
+        for keyword in invalid {
+            let err = text_data(keyword, b"x").unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<CommandError>(),
+                Some(CommandError::InvalidKeyword(_))
+            ));
+        }
+        assert!(text_data(&"k".repeat(79), b"x").is_ok());
+        assert!(text_data("Author name", b"x").is_ok());
+        assert!(text_data("Comment", b"a\0b").is_err());
+    }
+
+    #[test]
+    fn test_text_layout_needs_its_chunk_type() {
+        let file = temp_path("text_layout_type.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        let mismatch = |err: Box<dyn std::error::Error>| {
+            matches!(
+                err.downcast_ref::<CommandError>(),
+                Some(CommandError::TextLayoutMismatch(_, _))
+            )
+        };
+
+        for (text, ztxt) in [(true, false), (false, true)] {
+            let args = EncodeArgs {
+                text,
+                ztxt,
+                keyword: Some("Comment".to_string()),
+                ..encode_args(&file, "plain")
+            };
+            assert!(mismatch(encode(args, Verbosity::Normal).unwrap_err()));
+            let args = DecodeArgs {
+                text,
+                ztxt,
+                ..decode_args(&file, "ruSt")
+            };
+            assert!(mismatch(decode(args, Verbosity::Normal).unwrap_err()));
+        }
+        let args = EncodeArgs {
+            ztxt: true,
+            chunk_type: "tEXt".to_string(),
+            keyword: Some("Comment".to_string()),
+            ..encode_args(&file, "plain")
+        };
+        assert!(mismatch(encode(args, Verbosity::Normal).unwrap_err()));
+        assert_eq!(fs::read(&file).unwrap(), testing_png().as_bytes());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_text_is_latin1() {
+        let data = text_data("Comment", "caf\u{e9}".as_bytes()).unwrap();
+        assert_eq!(data, b"Comment\0caf\xe9");
+        let err = text_data("Comment", "\u{20ac}uro".as_bytes()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::InvalidText("Latin-1"))
+        ));
+        assert!(ztxt_data("Comment", &[0xe9]).is_err());
+
+        let file = temp_path("decode_latin1_text.png");
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(ChunkType::from_str("tEXt").unwrap(), data));
+        let ztxt = ztxt_data("Comment", "na\u{ef}ve".as_bytes()).unwrap();
+        png.append_chunk(Chunk::new(ChunkType::from_str("zTXt").unwrap(), ztxt));
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        for (chunk_type, expected) in [("tEXt", "caf\u{e9}"), ("zTXt", "na\u{ef}ve")] {
+            let args = DecodeArgs {
+                text: chunk_type == "tEXt",
+                ztxt: chunk_type == "zTXt",
+                ..decode_args(&file, chunk_type)
+            };
+            assert_eq!(
+                decode(args, Verbosity::Normal).unwrap(),
+                DecodeOutcome::Messages {
+                    keyword: Some("Comment".to_string()),
+                    messages: vec![expected.to_string()],
+                }
+            );
+        }
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_message_json() {
+        let chunk = Chunk::from_type_and_str("ruSt", "hello").unwrap();
+        let json = message_json(&chunk, b"hello".to_vec());
+        assert_eq!(json["type"], "ruSt");
+        assert_eq!(json["length"], 5);
+        assert_eq!(json["crc"], chunk.crc());
+        assert_eq!(json["utf8"], true);
+        assert_eq!(json["message"], "hello");
+        assert!(json.get("base64").is_none());
+
+        let binary: Vec<u8> = vec![0, 159, 146, 150, 255];
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), binary.clone());
+        let json = message_json(&chunk, binary);
+        assert_eq!(json["utf8"], false);
+        assert_eq!(json["base64"], "AJ+Slv8=");
+        assert!(json.get("message").is_none());
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let before = testing_png();
+        assert!(diff_lines(&before, &testing_png()).is_empty());
+
+        let mut after = testing_png();
+        after.append_chunk(Chunk::from_type_and_str("ruSt", "hidden").unwrap());
+        let lines = diff_lines(&before, &after);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("+ ruSt (6 bytes"));
+
+        let lines = diff_lines(&after, &before);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("- ruSt"));
+
+        let mut changed = testing_png();
+        changed.append_chunk(Chunk::from_type_and_str("ruSt", "other message").unwrap());
+        let lines = diff_lines(&after, &changed);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("~ ruSt data differs: 6 -> 13 bytes"));
+    }
+
+    #[test]
+    fn test_diff_stdin_twice() {
+        let args = DiffArgs {
+            before: PathBuf::from("-"),
+            after: PathBuf::from("-"),
+        };
+        let err = diff(args, Verbosity::Normal).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::StdinTwice)
+        ));
+    }
+
+    #[test]
+    fn test_rekey() {
+        let file = temp_path("rekey.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        let args = EncodeArgs {
+            key: Some("old".to_string()),
+            ..encode_args(&file, "hidden message")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+        let args = EncodeArgs {
+            text: true,
+            chunk_type: "tEXt".to_string(),
+            keyword: Some("Comment".to_string()),
+            ..encode_args(&file, "plain")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+
+        let outcome = rekey(RekeyArgs {
+            file: file.clone(),
+            old_key: "old".to_string(),
+            new_key: "new".to_string(),
+            output: None,
+            in_place: true,
+        })
+        .unwrap();
+        assert_eq!(outcome.rekeyed, 1);
+
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(png.chunk_by_type("tEXt").unwrap().data(), b"Comment\0plain");
+        let messages = decode_all_with_key(&png, "new");
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].1, "hidden message");
+        assert!(decode_all_with_key(&png, "old")
+            .iter()
+            .all(|(_, message)| message != "hidden message"));
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_rekey_skips_standard_chunks() {
+        let mut png = testing_png();
+        let text = cipher::xor_encode(b"Comment readable under the key", "old");
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("tEXt").unwrap(),
+            text.clone(),
+        ));
+        png.append_chunk(Chunk::from_type_and_str("ruSt", "plain message").unwrap());
+        assert!(readable_with_key(png.chunk_by_type("tEXt").unwrap(), "old").is_some());
+
+        assert_eq!(rekey_chunks(&mut png, "old", "new").unwrap(), 0);
+        assert_eq!(png.chunk_by_type("tEXt").unwrap().data(), text.as_slice());
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"plain message");
+
+        let err = rekey_chunks(&mut png, "", "new").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::EmptyOldKey)
+        ));
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"plain message");
+    }
+
+    #[test]
+    fn test_completion_script() {
+        use clap_complete::Shell;
+
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+            let script = String::from_utf8(completion_script(shell)).unwrap();
+            assert!(script.contains("encode"), "{}", shell);
+        }
+    }
+
+    #[test]
+    fn test_encode_base64_round_trip() {
+        let file = temp_path("encode_base64.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        let blob: Vec<u8> = vec![0, 159, 146, 150, 255];
+        let text = base64::engine::general_purpose::STANDARD.encode(&blob);
+
+        let args = EncodeArgs {
+            base64: true,
+            ..encode_args(&file, &text)
+        };
+        encode(args, Verbosity::Normal).unwrap();
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), blob);
+
+        let decode_args = |grep: &str| DecodeArgs {
+            file: file.clone(),
+            chunk_type: Some("ruSt".to_string()),
+            key: None,
+            output: None,
+            spread: false,
+            compress: false,
+            base64: true,
+            all: false,
+            grep: Some(grep.to_string()),
+            encoding: TextEncoding::Utf8,
+            cipher: None,
+            key_stdin: false,
+            offset: None,
+            try_all_with_key: None,
+            ignore_case: false,
+            ztxt: false,
+            text: false,
+            json: false,
+        };
+        assert!(decode(decode_args(&text), Verbosity::Normal).is_ok());
+
+        let args = EncodeArgs {
+            base64: true,
+            ..encode_args(&file, "not base64!")
+        };
+        let err = encode(args, Verbosity::Normal).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::InvalidBase64(_))
+        ));
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_decode_grep() {
+        let file = temp_path("decode_grep.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        encode(encode_args(&file, "meet at noon"), Verbosity::Normal).unwrap();
+
+        let grep_args = |pattern: &str| DecodeArgs {
+            file: file.clone(),
+            chunk_type: Some("ruSt".to_string()),
+            key: None,
+            output: None,
+            spread: false,
+            compress: false,
+            base64: false,
+            all: false,
+            grep: Some(pattern.to_string()),
+            encoding: TextEncoding::Utf8,
+            cipher: None,
+            key_stdin: false,
+            offset: None,
+            try_all_with_key: None,
+            ignore_case: false,
+            ztxt: false,
+            text: false,
+            json: false,
+        };
+        assert_eq!(
+            decode(grep_args("noon"), Verbosity::Normal).unwrap(),
+            DecodeOutcome::Messages {
+                keyword: None,
+                messages: vec!["meet at noon".to_string()],
+            }
+        );
+        let args = DecodeArgs {
+            chunk_type: Some("nOne".to_string()),
+            grep: None,
+            ..grep_args("")
+        };
+        assert_eq!(
+            decode(args, Verbosity::Normal).unwrap(),
+            DecodeOutcome::NotFound("nOne".to_string())
+        );
+        let err = decode(grep_args("midnight"), Verbosity::Normal).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::NoMatch(_))
+        ));
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "encoding")]
+    fn test_decode_text_latin1() {
+        let message = vec![b'c', b'a', b'f', 0xe9];
+        assert_eq!(
+            decode_text(message.clone(), TextEncoding::Latin1).unwrap(),
+            "café"
+        );
+        assert!(decode_text(message, TextEncoding::Utf8).is_err());
+        assert_eq!(
+            decode_text(vec![b'h', 0, b'i', 0], TextEncoding::Utf16le).unwrap(),
+            "hi"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "aes")]
+    fn test_encode_aes_round_trip() {
+        let file = temp_path("encode_aes.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        let args = EncodeArgs {
+            key: Some("passphrase".to_string()),
+            cipher: Some(CipherKind::Aes),
+            ..encode_args(&file, "encrypted message")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        let data = png.chunk_by_type("ruSt").unwrap().data();
+        assert_eq!(
+            unwrap_message(data, Some("passphrase"), CipherKind::Aes, false).unwrap(),
+            b"encrypted message"
+        );
+        let err = unwrap_message(data, Some("wrong"), CipherKind::Aes, false).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<pngme::PngMeError>(),
+            Some(pngme::PngMeError::Cipher(_))
+        ));
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_read_key_line() {
+        let stdin = Cursor::new(b"secret key\r\nmessage\n".to_vec());
+        assert_eq!(read_key_line(stdin).unwrap(), "secret key");
+
+        let err = read_key_line(Cursor::new(b"\n".to_vec())).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::EmptyKey)
+        ));
+        assert!(read_key_line(Cursor::new(Vec::new())).is_err());
+    }
+
+    #[test]
+    fn test_key_stdin_with_other_stdin_input() {
+        let file = temp_path("key_stdin_taken.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        let is_taken = |err: Box<dyn std::error::Error>| {
+            matches!(
+                err.downcast_ref::<CommandError>(),
+                Some(CommandError::StdinTaken)
+            )
+        };
+
+        let args = EncodeArgs {
+            message: Some("-".to_string()),
+            key_stdin: true,
+            ..encode_args(&file, "unused")
+        };
+        assert!(is_taken(encode(args, Verbosity::Normal).unwrap_err()));
+        let args = EncodeArgs {
+            file: PathBuf::from("-"),
+            output: Some(temp_path("key_stdin_taken.out.png")),
+            in_place: false,
+            key_stdin: true,
+            ..encode_args(&file, "message")
+        };
+        assert!(is_taken(encode(args, Verbosity::Normal).unwrap_err()));
+        let args = DecodeArgs {
+            key_stdin: true,
+            ..decode_args(Path::new("-"), "ruSt")
+        };
+        assert!(is_taken(decode(args, Verbosity::Normal).unwrap_err()));
+        assert_eq!(fs::read(&file).unwrap(), testing_png().as_bytes());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_compress_round_trip() {
+        let file = temp_path("encode_compress.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        let message = "repeat ".repeat(100);
+
+        let args = EncodeArgs {
+            compress: true,
+            key: Some("key".to_string()),
+            ..encode_args(&file, &message)
+        };
+        encode(args, Verbosity::Normal).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        let stored = png.chunk_by_type("ruSt").unwrap().data();
+        assert!(stored.len() < message.len());
+        assert_eq!(
+            unwrap_message(stored, Some("key"), CipherKind::Xor, true).unwrap(),
+            message.as_bytes()
+        );
+        assert!(unwrap_message(b"plain", None, CipherKind::Xor, true).is_err());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_parts_reassembles() {
+        let file = temp_path("encode_parts.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+
+        let args = EncodeArgs {
+            parts: NonZeroUsize::new(3),
+            ..encode_args(&file, "a message in parts")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        let parts = png.chunks_by_type("ruSt");
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].length(), 6);
+        assert_eq!(parts[2].length(), 6);
+        let message: Vec<u8> = parts
+            .iter()
+            .flat_map(|chunk| chunk.data())
+            .copied()
+            .collect();
+        assert_eq!(message, b"a message in parts");
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_split_parts_remainder() {
+        let parts = split_parts(b"abcdefgh", 3).unwrap();
+        assert_eq!(parts, vec![&b"abc"[..], b"def", b"gh"]);
+        assert_eq!(split_parts(b"abc", 3).unwrap(), vec![&b"a"[..], b"b", b"c"]);
+        let err = split_parts(b"a", 3).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::TooManyParts(3, 1))
+        ));
+    }
+
+    #[test]
+    fn test_encode_rejects_empty_message() {
+        let file = temp_path("encode_empty.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+
+        let result = encode(encode_args(&file, ""), Verbosity::Normal);
+        assert!(result.is_err());
+        assert_eq!(fs::read(&file).unwrap(), testing_png().as_bytes());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_gzipped_png() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let file = temp_path("encode_gzipped.png.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&testing_png().as_bytes()).unwrap();
+        fs::write(&file, encoder.finish().unwrap()).unwrap();
+
+        encode(encode_args(&file, "hidden"), Verbosity::Normal).unwrap();
+
+        assert!(fs::read(&file).unwrap().starts_with(&Png::GZIP_MAGIC));
+        let png = Png::from_file(&file).unwrap();
+        assert!(png.is_gzipped());
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"hidden");
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_quiet_suppresses_status() {
+        let file = temp_path("quiet_status.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+
+        let outcome = encode(encode_args(&file, "hidden"), Verbosity::Quiet).unwrap();
+        assert_eq!(status_line(&outcome, Verbosity::Quiet), None);
+        assert_eq!(
+            status_line(&outcome, Verbosity::Normal).as_deref(),
+            Some("Chunk 'ruSt' added")
+        );
+        assert!(detail_lines(&testing_png(), None, None, Verbosity::Quiet).is_empty());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_verbose_adds_details() {
+        let png = testing_png();
+        assert!(detail_lines(&png, None, None, Verbosity::Normal).is_empty());
+        assert_eq!(
+            detail_lines(&png, None, None, Verbosity::Verbose),
+            [
+                "Chunk 0 'IHDR' at offset 8: 13 byte(s) of data, CRC 1f15c489",
+                "Chunk 1 'IDAT' at offset 33: 5 byte(s) of data, CRC 3c50ff95",
+                "Chunk 2 'IEND' at offset 50: 0 byte(s) of data, CRC ae426082",
+            ]
+        );
+        assert_eq!(
+            detail_lines(&png, Some("IEND"), None, Verbosity::Verbose),
+            ["Chunk 2 'IEND' at offset 50: 0 byte(s) of data, CRC ae426082"]
+        );
+        // Offsets are in the file the PNG is embedded in
+        assert_eq!(
+            detail_lines(&png, Some("IEND"), Some(6), Verbosity::Verbose),
+            ["Chunk 2 'IEND' at offset 56: 0 byte(s) of data, CRC ae426082"]
+        );
+    }
+
+    #[test]
+    fn test_encode_outcome() {
+        let file = temp_path("encode_outcome.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+
+        let outcome = encode(encode_args(&file, "hidden"), Verbosity::Normal).unwrap();
+        assert_eq!(
+            outcome,
+            EncodeOutcome {
+                chunk_type: "ruSt".to_string(),
+                chunks_added: 1,
+                output: file.clone(),
+                bytes_written: fs::metadata(&file).unwrap().len() as usize,
+                size_delta: 18,
+                written: true,
+                duplicate: false,
+                appended: false,
+            }
+        );
+        assert_eq!(outcome.to_string(), "Chunk 'ruSt' added");
+        let parts = EncodeOutcome {
+            chunks_added: 3,
+            ..outcome
+        };
+        assert_eq!(parts.to_string(), "3 Chunk(s) 'ruSt' added");
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_size_delta_matches_applied_change() {
+        let file = temp_path("size_delta.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        let size = || fs::metadata(&file).unwrap().len() as i64;
+
+        let before = size();
+        let args = EncodeArgs {
+            size_delta: true,
+            ..encode_args(&file, "hidden")
+        };
+        let outcome = encode(args, Verbosity::Normal).unwrap();
+        assert!(!outcome.written);
+        assert_eq!(size(), before);
+        encode(encode_args(&file, "hidden"), Verbosity::Normal).unwrap();
+        assert_eq!(size() - before, outcome.size_delta);
+
+        let before = size();
+        let args = RemoveArgs {
+            size_delta: true,
+            ..remove_args(&file, &["ruSt"])
+        };
+        let outcome = remove(args, Verbosity::Normal).unwrap();
+        assert_eq!(size(), before);
+        assert!(outcome.to_string().ends_with("by -18 bytes"));
+        remove(remove_args(&file, &["ruSt"]), Verbosity::Normal).unwrap();
+        assert_eq!(size() - before, outcome.size_delta);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_dry_run_leaves_file_unchanged() {
+        let file = temp_path("dry_run.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        let original = fs::read(&file).unwrap();
+
+        let args = EncodeArgs {
+            dry_run: true,
+            ..encode_args(&file, "hidden")
+        };
+        let outcome = encode(args, Verbosity::Normal).unwrap();
+        assert!(!outcome.written);
+        assert!(outcome.to_string().contains("would change the file size"));
+        assert_eq!(fs::read(&file).unwrap(), original);
+
+        encode(encode_args(&file, "hidden"), Verbosity::Normal).unwrap();
+        let encoded = fs::read(&file).unwrap();
+        let args = RemoveArgs {
+            dry_run: true,
+            ..remove_args(&file, &["ruSt"])
+        };
+        let outcome = remove(args, Verbosity::Normal).unwrap();
+        assert_eq!(outcome.removed, vec![("ruSt".to_string(), 1)]);
+        assert!(outcome.to_string().contains("would be removed"));
+        assert_eq!(fs::read(&file).unwrap(), encoded);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_replace_keeps_position() {
+        let file = temp_path("replace.png");
+        let mut png = testing_png();
+        png.append_chunk(Chunk::from_type_and_str("ruSt", "old message").unwrap());
+        png.append_chunk(Chunk::from_type_and_str("abCd", "after").unwrap());
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = |chunk_type: &str| ReplaceArgs {
+            file: file.clone(),
+            chunk_type: chunk_type.to_string(),
+            message: "new message".to_string(),
+            key: None,
+            output: None,
+            in_place: true,
+        };
+        let outcome = replace(args("ruSt")).unwrap();
+        assert_eq!(outcome.to_string(), "Message of Chunk 'ruSt' replaced");
+
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "ruSt", "abCd", "IEND"]);
+        assert_eq!(png.chunks()[2].data_as_string().unwrap(), "new message");
+
+        assert!(replace(args("efGh")).is_err());
+        assert_eq!(Png::from_file(&file).unwrap().as_bytes(), png.as_bytes());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_remove_outcome() {
+        let file = temp_path("remove_outcome.png");
+        let mut png = testing_png();
+        for message in ["1", "2"] {
+            png.append_chunk(Chunk::new(
+                ChunkType::from_str("abCd").unwrap(),
+                message.into(),
+            ));
+        }
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = RemoveArgs {
+            all: true,
+            ..remove_args(&file, &["abCd"])
+        };
+        let outcome = remove(args, Verbosity::Normal).unwrap();
+        assert_eq!(outcome.removed, vec![("abCd".to_string(), 2)]);
+        assert_eq!(outcome.to_string(), "2 Chunk(s) 'abCd' removed");
+
+        fs::write(&file, png.as_bytes()).unwrap();
+        let outcome = remove(remove_args(&file, &["abCd"]), Verbosity::Normal).unwrap();
+        assert_eq!(outcome.to_string(), "Chunk 'abCd' removed");
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_keeps_iend_last() {
+        let file = temp_path("encode_iend_last.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+
+        encode(encode_args(&file, "hidden"), Verbosity::Normal).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        let last = png.chunks().last().unwrap();
+        assert_eq!(last.chunk_type().to_string(), "IEND");
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"hidden");
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_allow_empty_message() {
+        let file = temp_path("encode_allow_empty.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+
+        let args = EncodeArgs {
+            allow_empty: true,
+            ..encode_args(&file, "")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().length(), 0);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_without_key_stores_message() {
+        let file = temp_path("encode_no_key.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+
+        encode(encode_args(&file, "plain message"), Verbosity::Normal).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"plain message");
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_with_key_round_trip() {
+        let file = temp_path("encode_key.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+
+        let args = EncodeArgs {
+            key: Some("secret".to_string()),
+            ..encode_args(&file, "plain message")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        let data = png.chunk_by_type("ruSt").unwrap().data();
+        assert_ne!(data, b"plain message");
+        assert!(data.iter().any(|byte| byte.is_ascii_control()));
+        assert_eq!(cipher::xor_decode(data, "secret"), "plain message");
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_expand_output_template() {
+        let file = Path::new("images/photos/cat.png");
+        assert_eq!(
+            expand_output_template("{dir}/out/{name}.{ext}", file),
+            PathBuf::from("images/photos/out/cat.png")
+        );
+        assert_eq!(
+            expand_output_template("{dir}/{name}-encoded.png", Path::new("cat.png")),
+            PathBuf::from("./cat-encoded.png")
+        );
+    }
+
+    #[test]
+    fn test_encode_message_file() {
+        let file = temp_path("encode_message_file.png");
+        let message_file = temp_path("encode_message_file.bin");
+        let payload: Vec<u8> = (0..=255).collect();
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        fs::write(&message_file, &payload).unwrap();
+
+        let args = EncodeArgs {
+            message: None,
+            message_file: Some(message_file.clone()),
+            ..encode_args(&file, "")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(
+            png.chunk_by_type("ruSt").unwrap().data(),
+            payload.as_slice()
+        );
+        fs::remove_file(file).unwrap();
+        fs::remove_file(message_file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_message_env() {
+        let file = temp_path("encode_message_env.png");
+        let name = "PNGME_TEST_MESSAGE_ENV";
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        std::env::set_var(name, "secret from the environment");
+
+        let args = EncodeArgs {
+            message: None,
+            message_env: Some(name.to_string()),
+            ..encode_args(&file, "")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(
+            png.chunk_by_type("ruSt").unwrap().data(),
+            b"secret from the environment"
+        );
+
+        let args = EncodeArgs {
+            message: None,
+            message_env: Some("PNGME_TEST_MESSAGE_ENV_UNSET".to_string()),
+            ..encode_args(&file, "")
+        };
+        let err = encode(args, Verbosity::Normal).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::MissingEnvVar(_))
+        ));
+        std::env::remove_var(name);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_decode_to_output_file() {
+        let file = temp_path("decode_output.png");
+        let message_file = temp_path("decode_output.in");
+        let output = temp_path("decode_output.out");
+        let payload: Vec<u8> = (0..=255).rev().collect();
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        fs::write(&message_file, &payload).unwrap();
+
+        let args = EncodeArgs {
+            message: None,
+            message_file: Some(message_file.clone()),
+            key: Some("secret".to_string()),
+            ..encode_args(&file, "")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+        decode(
+            DecodeArgs {
+                file: file.clone(),
+                chunk_type: Some("ruSt".to_string()),
+                key: Some("secret".to_string()),
+                output: Some(output.clone()),
+                spread: false,
+                compress: false,
+                base64: false,
+                all: false,
+                grep: None,
+                encoding: TextEncoding::Utf8,
+                cipher: None,
+                key_stdin: false,
+                offset: None,
+                try_all_with_key: None,
+                ignore_case: false,
+                ztxt: false,
+                text: false,
+                json: false,
+            },
+            Verbosity::Normal,
+        )
+        .unwrap();
+
+        assert_eq!(fs::read(&output).unwrap(), payload);
+        fs::remove_file(file).unwrap();
+        fs::remove_file(message_file).unwrap();
+        fs::remove_file(output).unwrap();
+    }
+
+    fn remove_args(file: &Path, chunk_types: &[&str]) -> RemoveArgs {
+        RemoveArgs {
+            file: file.to_path_buf(),
+            chunk_types: chunk_types.iter().map(|s| s.to_string()).collect(),
+            all: false,
+            force: false,
+            ignore_case: false,
+            output: None,
+            output_template: None,
+            in_place: true,
+            size_delta: false,
+            dry_run: false,
+            log: None,
+        }
+    }
+
+    #[test]
+    fn test_remove_critical_chunk_requires_force() {
+        let file = temp_path("remove_critical.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+
+        assert!(remove(remove_args(&file, &["IEND"]), Verbosity::Normal).is_err());
+        assert_eq!(fs::read(&file).unwrap(), testing_png().as_bytes());
+
+        let args = RemoveArgs {
+            force: true,
+            ..remove_args(&file, &["IEND"])
+        };
+        remove(args, Verbosity::Normal).unwrap();
+        let (png, _) = Png::from_bytes_lenient(&fs::read(&file).unwrap()).unwrap();
+        assert!(png.chunk_by_type("IEND").is_none());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_remove_multiple_types() {
+        let file = temp_path("remove_multiple.png");
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("abCd").unwrap(),
+            b"1".to_vec(),
+        ));
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("efGh").unwrap(),
+            b"2".to_vec(),
+        ));
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ijKl").unwrap(),
+            b"3".to_vec(),
+        ));
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        remove(remove_args(&file, &["abCd", "ijKl"]), Verbosity::Normal).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        assert!(png.chunk_by_type("abCd").is_none());
+        assert!(png.chunk_by_type("ijKl").is_none());
+        assert!(png.chunk_by_type("efGh").is_some());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_remove_all_flag() {
+        let file = temp_path("remove_all.png");
+        let mut png = testing_png();
+        for message in ["1", "2", "3"] {
+            png.append_chunk(Chunk::new(
+                ChunkType::from_str("abCd").unwrap(),
+                message.into(),
+            ));
+        }
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = RemoveArgs {
+            all: true,
+            ..remove_args(&file, &["abCd", "efGh"])
+        };
+        remove(args, Verbosity::Normal).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), testing_png().as_bytes());
+        assert!(remove(remove_args(&file, &["efGh"]), Verbosity::Normal).is_err());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_load_png_from_stdin() {
+        let bytes = testing_png().as_bytes();
+        let png = load_png_from(Path::new("-"), Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(png.as_bytes(), bytes);
+
+        let garbage = Cursor::new(b"not a png".to_vec());
+        assert!(load_png_from(Path::new("-"), garbage).is_err());
+    }
+
+    #[test]
+    fn test_stdin_requires_output() {
+        let stdin = Path::new("-");
+        let err = encode(encode_args(stdin, "message"), Verbosity::Normal).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::StdinWithoutOutput)
+        ));
+
+        let err = remove(remove_args(stdin, &["abCd"]), Verbosity::Normal).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::StdinWithoutOutput)
+        ));
+    }
+
+    #[test]
+    fn test_in_place_required_without_output() {
+        let file = temp_path("in_place_required.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+
+        let args = EncodeArgs {
+            in_place: false,
+            ..encode_args(&file, "message")
+        };
+        let err = encode(args, Verbosity::Normal).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::InPlaceRequired(_))
+        ));
+        assert_eq!(fs::read(&file).unwrap(), testing_png().as_bytes());
+
+        encode(encode_args(&file, "message"), Verbosity::Normal).unwrap();
+        let encoded = fs::read(&file).unwrap();
+        let args = RemoveArgs {
+            in_place: false,
+            ..remove_args(&file, &["ruSt"])
+        };
+        let err = remove(args, Verbosity::Normal).unwrap_err();
+        assert!(err.to_string().contains("--in-place"));
+        assert_eq!(fs::read(&file).unwrap(), encoded);
+        fs::remove_file(file).unwrap();
+    }
+
+    /// Checks that a command writing back to `file` failed for lack of `--in-place`
+    /// and left the file holding `bytes`
+    fn assert_in_place_required<T: fmt::Debug>(result: Result<T>, file: &Path, bytes: &[u8]) {
+        let err = result.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::InPlaceRequired(_))
+        ));
+        assert_eq!(fs::read(file).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_replace_requires_in_place() {
+        let file = temp_path("replace_in_place.png");
+        let mut png = testing_png();
+        png.append_chunk(Chunk::from_type_and_str("ruSt", "old message").unwrap());
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = ReplaceArgs {
+            file: file.clone(),
+            chunk_type: "ruSt".to_string(),
+            message: "new message".to_string(),
+            key: None,
+            output: None,
+            in_place: false,
+        };
+        assert_in_place_required(replace(args), &file, &png.as_bytes());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_dedup_requires_in_place() {
+        let file = temp_path("dedup_in_place.png");
+        let mut png = testing_png();
+        for _ in 0..2 {
+            png.append_chunk(Chunk::from_type_and_str("ruSt", "twice").unwrap());
+        }
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = DedupArgs {
+            file: file.clone(),
+            keep_last: false,
+            output: None,
+            in_place: false,
+            log: None,
+        };
+        assert_in_place_required(dedup(args), &file, &png.as_bytes());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_namespace_requires_in_place() {
+        let file = temp_path("namespace_in_place.png");
+        let mut png = testing_png();
+        png.append_chunk(Chunk::from_type_and_str("ruSt", "custom").unwrap());
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = NamespaceArgs {
+            file: file.clone(),
+            prefix: "ab".to_string(),
+            output: None,
+            in_place: false,
+        };
+        assert_in_place_required(namespace(args), &file, &png.as_bytes());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_trailer_strip_requires_in_place() {
+        let file = temp_path("trailer_in_place.png");
+        let mut bytes = testing_png().as_bytes();
+        bytes.extend_from_slice(b"appended");
+        fs::write(&file, &bytes).unwrap();
+
+        let args = TrailerArgs {
+            file: file.clone(),
+            offset: None,
+            strip: true,
+            output: None,
+            in_place: false,
+        };
+        assert_in_place_required(trailer(args), &file, &bytes);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_repair_gzipped_png() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let file = temp_path("repair_gzipped.png.gz");
+        let mut bytes = testing_png().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&bytes).unwrap();
+        fs::write(&file, encoder.finish().unwrap()).unwrap();
+
+        let args = RepairArgs {
+            file: file.clone(),
+            report: false,
+            output: None,
+            in_place: true,
+        };
+        repair(args, Verbosity::Normal).unwrap();
+
+        assert!(fs::read(&file).unwrap().starts_with(&Png::GZIP_MAGIC));
+        let png = Png::from_file(&file).unwrap();
+        assert!(png.is_gzipped());
+        assert_eq!(png.as_bytes(), testing_png().as_bytes());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_repair_truncated_file() {
+        let file = temp_path("repair_truncated.png");
+        let mut png = testing_png();
+        png.append_chunk(Chunk::from_type_and_str("ruSt", "kept").unwrap());
+        let bytes = png.as_bytes();
+        fs::write(&file, &bytes[..bytes.len() - 3]).unwrap();
+        assert!(Png::from_file(&file)
+            .unwrap_err()
+            .to_string()
+            .contains("pngme repair"));
+
+        let args = RepairArgs {
+            file: file.clone(),
+            report: false,
+            output: None,
+            in_place: true,
+        };
+        let outcome = repair(args, Verbosity::Normal).unwrap();
+        assert_eq!(outcome.dropped, Chunk::MIN_BYTES - 3);
+        assert!(outcome.crc_errors.is_empty());
+        assert_eq!(fs::read(&file).unwrap(), bytes);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_repair_requires_in_place() {
+        let file = temp_path("repair_in_place.png");
+        let mut bytes = testing_png().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        fs::write(&file, &bytes).unwrap();
+
+        let args = RepairArgs {
+            file: file.clone(),
+            report: false,
+            output: None,
+            in_place: false,
+        };
+        assert_in_place_required(repair(args, Verbosity::Normal), &file, &bytes);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_find_limit() {
+        let file = temp_path("find_limit.png");
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![0xff]));
+        for message in ["one", "two"] {
+            png.append_chunk(Chunk::from_type_and_str("ruSt", message).unwrap());
+        }
+        fs::write(&file, png.as_bytes()).unwrap();
+        let args = |limit, stream| FindArgs {
+            file: file.clone(),
+            offset: None,
+            limit,
+            utf8_only: false,
+            chunk_type: None,
+            ignore_case: false,
+            stream,
+        };
+        let data = |outcome: &FindOutcome| -> Vec<Vec<u8>> {
+            outcome
+                .candidates
+                .iter()
+                .map(|(_, chunk)| chunk.data().to_vec())
+                .collect()
+        };
+
+        // Only the first two candidates are kept, then ranked
+        let outcome = find(args(Some(2), false), Verbosity::Normal).unwrap();
+        assert_eq!(data(&outcome), [b"one".to_vec(), vec![0xff]]);
+        assert_eq!(outcome.omitted, 1);
+        assert!(outcome.to_string().ends_with("\n... and 1 more"));
+
+        let outcome = find(args(Some(2), true), Verbosity::Normal).unwrap();
+        assert_eq!(data(&outcome), [vec![0xff], b"one".to_vec()]);
+
+        let outcome = find(args(None, false), Verbosity::Normal).unwrap();
+        assert_eq!(
+            data(&outcome),
+            [b"one".to_vec(), b"two".to_vec(), vec![0xff]]
+        );
+        assert_eq!(outcome.omitted, 0);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_write_jsonl() {
+        let png = testing_png();
+        let mut out: Vec<u8> = Vec::new();
+        write_jsonl(
+            Png::stream_chunks(Cursor::new(png.as_bytes())).unwrap(),
+            &mut out,
+        )
+        .unwrap();
+
+        let lines: Vec<&str> = std::str::from_utf8(&out).unwrap().lines().collect();
+        assert_eq!(lines.len(), png.chunks().len());
+        for (line, chunk) in lines.iter().zip(png.chunks()) {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(value["type"], chunk.chunk_type().to_string());
+        }
+    }
+
+    #[test]
+    fn test_print_jsonl_streams_embedded_png() {
+        let file = temp_path("print_jsonl.png");
+        let mut bytes = b"prefix".to_vec();
+        bytes.extend_from_slice(&testing_png().as_bytes());
+        fs::write(&file, &bytes).unwrap();
+        let args = |offset| PrintArgs {
+            file: file.clone(),
+            offset,
+            since_offset: None,
+            json: false,
+            jsonl: true,
+            types_only: false,
+            checksum: false,
+            multi: false,
+            no_validate_type: false,
+        };
+
+        let mut out: Vec<u8> = Vec::new();
+        print_jsonl(args(Some(6)), &mut out).unwrap();
+        assert_eq!(out.iter().filter(|&&byte| byte == b'\n').count(), 3);
+
+        let mut png = testing_png();
+        png.remove_chunk("IEND").unwrap();
+        fs::write(&file, png.as_bytes()).unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        let err = print_jsonl(args(None), &mut out).unwrap_err();
+        assert!(err.to_string().contains("no IEND Chunk"));
+        assert_eq!(out.iter().filter(|&&byte| byte == b'\n').count(), 2);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_chunk_types() {
+        let mut png = testing_png();
+        png.append_chunk(Chunk::from_type_and_str("ruSt", "message").unwrap());
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "ruSt", "IEND"]);
+    }
+
+    #[test]
+    fn test_spread_round_trip() {
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("abCd").unwrap(),
+            b"one".to_vec(),
+        ));
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("gAMA").unwrap(),
+            vec![0, 0, 0xb1, 0x8f],
+        ));
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("efGh").unwrap(),
+            b"two".to_vec(),
+        ));
+        let message = b"spread over two chunks";
+
+        let layout = spread_data(&mut png, message).unwrap();
+        assert_eq!(layout.len(), 24);
+        assert_eq!(png.chunk_by_type("gAMA").unwrap().data().len(), 4);
+        assert!(png
+            .chunk_by_type("abCd")
+            .unwrap()
+            .data()
+            .starts_with(b"one"));
+        assert!(png
+            .chunk_by_type("efGh")
+            .unwrap()
+            .data()
+            .starts_with(b"two"));
+
+        let mut png = Png::try_from(png.as_bytes().as_slice()).unwrap();
+        assert_eq!(gather_data(&png, &layout).unwrap(), message);
+
+        // The layout still matches after a chunk is inserted before the carriers
+        png.insert_chunk(1, Chunk::from_type_and_str("zzZz", "new").unwrap())
+            .unwrap();
+        assert_eq!(gather_data(&png, &layout).unwrap(), message);
+    }
+
+    #[test]
+    fn test_encode_appends_audit_log() {
+        let file = temp_path("encode_log.png");
+        let log = temp_path("encode_log.log");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        let _ = fs::remove_file(&log);
+
+        for message in ["first", "second"] {
+            let args = EncodeArgs {
+                log: Some(log.clone()),
+                ..encode_args(&file, message)
+            };
+            encode(args, Verbosity::Normal).unwrap();
+        }
+
+        let contents = fs::read_to_string(&log).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.contains("\tencode\t")));
+        assert!(lines.iter().all(|line| line.ends_with("\truSt")));
+
+        let output = temp_path("encode_log_output.png");
+        let args = EncodeArgs {
+            output: Some(output.clone()),
+            in_place: false,
+            log: Some(log.clone()),
+            ..encode_args(&file, "third")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+        let contents = fs::read_to_string(&log).unwrap();
+        let line = contents.lines().last().unwrap();
+        assert!(line.contains(&format!("\t{}\t", output.display())));
+        fs::remove_file(file).unwrap();
+        fs::remove_file(output).unwrap();
+        fs::remove_file(log).unwrap();
+    }
+
+    #[test]
+    fn test_encode_at_offset() {
+        let file = temp_path("encode_at_offset.png");
+        let original = testing_png().as_bytes();
+        fs::write(&file, &original).unwrap();
+
+        // Lands in the middle of the IHDR data
+        let offset = Png::HEADER_LENGHT + 10;
+        let args = EncodeArgs {
+            at_offset: Some(offset),
+            ..encode_args(&file, "spliced")
+        };
+        encode(args, Verbosity::Normal).unwrap();
+
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"spliced".to_vec());
+        let chunk_bytes = chunk.as_bytes();
+        let actual = fs::read(&file).unwrap();
+        assert_eq!(&actual[..offset], &original[..offset]);
+        assert_eq!(
+            &actual[offset..offset + chunk_bytes.len()],
+            &chunk_bytes[..]
+        );
+        assert_eq!(&actual[offset + chunk_bytes.len()..], &original[offset..]);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_at_offset_out_of_range() {
+        let file = temp_path("encode_at_offset_range.png");
+        let original = testing_png().as_bytes();
+        fs::write(&file, &original).unwrap();
+
+        let args = EncodeArgs {
+            at_offset: Some(original.len() + 1),
+            ..encode_args(&file, "spliced")
+        };
+        assert!(encode(args, Verbosity::Normal).is_err());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_extract_index() {
+        let first: Vec<u8> = vec![0x89, b'P', b'N', b'G', 0, 0xff];
+        let second: Vec<u8> = (0..=255).collect();
+        let mut png = testing_png();
+        for data in [&first, &second] {
+            png.append_chunk(Chunk::new(
+                ChunkType::from_str("ruSt").unwrap(),
+                data.clone(),
+            ));
+        }
+        let file = temp_path("extract_index.png");
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let output = temp_path("extract_index.out");
+        let args = |index| ExtractArgs {
+            file: file.clone(),
+            chunk_type: "ruSt".to_string(),
+            output: output.clone(),
+            index,
+            offset: None,
+            format: DataFormat::Raw,
+        };
+        let outcome = extract(args(None), Verbosity::Normal).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), first);
+        assert_eq!(outcome.found, 2);
+        assert!(outcome.warning().is_some());
+        let outcome = extract(args(Some(1)), Verbosity::Normal).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), second);
+        assert_eq!(outcome.warning(), None);
+        assert!(extract(args(Some(2)), Verbosity::Normal).is_err());
+
+        fs::remove_file(output).unwrap();
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_extract_formats() {
+        let data: Vec<u8> = vec![0, 159, 146, 150, 255, 10];
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            data.clone(),
+        ));
+
+        let file = temp_path("extract_formats.png");
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        for format in [DataFormat::Raw, DataFormat::Base64, DataFormat::Hex] {
+            let output = temp_path("extract_formats.out");
+            extract(
+                ExtractArgs {
+                    file: file.clone(),
+                    chunk_type: "ruSt".to_string(),
+                    output: output.clone(),
+                    index: None,
+                    offset: None,
+                    format,
+                },
+                Verbosity::Normal,
+            )
+            .unwrap();
+
+            let written = fs::read(&output).unwrap();
+            let decoded: Vec<u8> = match format {
+                DataFormat::Raw => written,
+                DataFormat::Base64 => base64::engine::general_purpose::STANDARD
+                    .decode(written)
+                    .unwrap(),
+                DataFormat::Hex => written
+                    .chunks(2)
+                    .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+                    .collect(),
+            };
+            assert_eq!(decoded, data, "format {:?}", format);
+            fs::remove_file(output).unwrap();
+        }
+        fs::remove_file(file).unwrap();
+    }
+
+    fn assert_stable_fingerprint(algorithm: HashAlgorithm, length: usize) {
+        let png = testing_png();
+        let mut encoded = testing_png();
+        encoded.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"hi".to_vec(),
+        ));
+
+        let digest = image_fingerprint(&png, algorithm).unwrap();
+        assert_eq!(digest.len(), length);
+        assert_eq!(digest, image_fingerprint(&png, algorithm).unwrap());
+        assert_eq!(digest, image_fingerprint(&encoded, algorithm).unwrap());
+    }
+
+    #[test]
+    fn test_fingerprint_sha256() {
+        assert_stable_fingerprint(HashAlgorithm::Sha256, 32);
+    }
+
+    #[test]
+    fn test_fingerprint_known_digest() {
+        let file = temp_path("fingerprint.png");
+        fs::write(&file, testing_png().as_bytes()).unwrap();
+        let png = Png::from_file(&file).unwrap();
+
+        // SHA-256 of "IHDR", the IHDR data, "IDAT" and the IDAT data of the fixture
+        assert_eq!(
+            to_hex(&image_fingerprint(&png, HashAlgorithm::Sha256).unwrap()),
+            "d73119235b7e3d6002905e422175508ae24fe68909c3199abd6345307a540fe8"
+        );
+        #[cfg(feature = "sha1")]
+        assert_eq!(
+            to_hex(&image_fingerprint(&png, HashAlgorithm::Sha1).unwrap()),
+            "2703370dd609c42676cf37e075945fecb2d28233"
+        );
+        fs::remove_file(file).unwrap();
+    }
+
+    #[cfg(feature = "sha1")]
+    #[test]
+    fn test_fingerprint_sha1() {
+        assert_stable_fingerprint(HashAlgorithm::Sha1, 20);
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn test_fingerprint_blake3() {
+        assert_stable_fingerprint(HashAlgorithm::Blake3, 32);
+    }
+
+    fn validate_args(file: &Path) -> ValidateArgs {
+        ValidateArgs {
+            file: file.to_path_buf(),
+            offset: None,
+            require_unique: Vec::new(),
+            verify_signature_only: false,
+            strict_order: false,
+            fail_fast: false,
+            collect_errors: false,
+        }
+    }
+
+    #[test]
+    fn test_validate_signature_only() {
+        let png_file = temp_path("signature.png");
+        let text_file = temp_path("signature.txt");
+        let mut bytes = testing_png().as_bytes();
+        bytes.truncate(20);
+        fs::write(&png_file, bytes).unwrap();
+        fs::write(&text_file, "not a png at all").unwrap();
+
+        let args = |file: &Path| ValidateArgs {
+            verify_signature_only: true,
+            ..validate_args(file)
+        };
+        let outcome = validate(args(&png_file), Verbosity::Normal).unwrap();
+        assert_eq!(
+            outcome,
+            ValidateOutcome {
+                file: png_file.clone(),
+                signature_only: true,
+            }
+        );
+        assert!(outcome.to_string().ends_with("has a valid PNG signature"));
+        assert!(validate(args(&text_file), Verbosity::Normal).is_err());
+        fs::remove_file(png_file).unwrap();
+        fs::remove_file(text_file).unwrap();
+    }
+
+    #[test]
+    fn test_validate_collect_errors() {
+        let file = temp_path("validate_collect.png");
+        let png = Png::from_chunks(
+            ["IHDR", "IDAT", "PLTE", "IEND"]
+                .iter()
+                .map(|chunk_type| Chunk::from_type_and_str(chunk_type, "").unwrap())
+                .collect(),
+        );
+        let mut bytes = png.as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        fs::write(&file, &bytes).unwrap();
+
+        let args = ValidateArgs {
+            strict_order: true,
+            collect_errors: true,
+            ..validate_args(&file)
+        };
+        let problems = validation_problems(&bytes, &args).unwrap();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("Chunk 3: stored CRC"));
+        assert!(problems[1].contains("PLTE must come before the first IDAT"));
+
+        let err = validate(args, Verbosity::Normal).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::ValidationFailed(problems)) if problems.len() == 2
+        ));
+        let args = ValidateArgs {
+            strict_order: true,
+            ..validate_args(&file)
+        };
+        assert!(validate(args, Verbosity::Normal)
+            .unwrap_err()
+            .to_string()
+            .contains("CRC"));
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_validate_end_of_file() {
+        let file = temp_path("validate_end.png");
+        let mut bytes = testing_png().as_bytes();
+        bytes.extend_from_slice(b"appended");
+        fs::write(&file, &bytes).unwrap();
+
+        // Other commands keep the data after IEND, only validate rejects it
+        encode(encode_args(&file, "hidden"), Verbosity::Normal).unwrap();
+        assert!(fs::read(&file).unwrap().ends_with(b"appended"));
+        let err = validate(validate_args(&file), Verbosity::Normal).unwrap_err();
+        assert!(err.to_string().contains("after the IEND Chunk"));
+
+        let mut png = testing_png();
+        png.remove_chunk("IEND").unwrap();
+        fs::write(&file, png.as_bytes()).unwrap();
+        let err = validate(validate_args(&file), Verbosity::Normal).unwrap_err();
+        assert!(err.to_string().contains("no IEND Chunk"));
+        let args = ValidateArgs {
+            collect_errors: true,
+            ..validate_args(&file)
+        };
+        let problems = validation_problems(&png.as_bytes(), &args).unwrap();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("no IEND Chunk"));
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_read_commands_at_offset() {
+        let file = temp_path("read_at_offset.png");
+        let png = testing_png();
+        let mut bytes = b"host prefix".to_vec();
+        bytes.extend_from_slice(&png.as_bytes());
+        bytes.extend_from_slice(b"host suffix");
+        fs::write(&file, &bytes).unwrap();
+        let offset = Some(b"host prefix".len());
+
+        for args in [
+            ValidateArgs {
+                offset,
+                ..validate_args(&file)
+            },
+            ValidateArgs {
+                offset,
+                collect_errors: true,
+                ..validate_args(&file)
+            },
+            ValidateArgs {
+                offset,
+                verify_signature_only: true,
+                ..validate_args(&file)
+            },
+        ] {
+            assert!(validate(args, Verbosity::Normal).is_ok());
+        }
+        assert!(validate(validate_args(&file), Verbosity::Normal).is_err());
+
+        let outcome = trailer(TrailerArgs {
+            file: file.clone(),
+            offset,
+            strip: false,
+            output: None,
+            in_place: false,
+        })
+        .unwrap();
+        assert_eq!(
+            outcome,
+            TrailerOutcome::Found {
+                file: file.clone(),
+                bytes: b"host suffix".len()
+            }
+        );
+
+        let outcome = fingerprint(FingerprintArgs {
+            file: file.clone(),
+            offset,
+            hash: HashAlgorithm::Sha256,
+        })
+        .unwrap();
+        assert_eq!(
+            outcome.digest,
+            image_fingerprint(&png, HashAlgorithm::Sha256).unwrap()
+        );
+        let outcome = detect(DetectArgs {
+            file: file.clone(),
+            offset,
+        })
+        .unwrap();
+        assert_eq!(outcome.score, 0);
+        let err = exif(ExifArgs {
+            file: file.clone(),
+            offset,
+            output: None,
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("eXIf"));
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_validate_require_unique() {
+        let file = temp_path("validate_unique.png");
+        let mut png = testing_png();
+
+        for expected_ok in [false, true, false] {
+            fs::write(&file, png.as_bytes()).unwrap();
+            let result = validate(
+                ValidateArgs {
+                    require_unique: vec!["ruSt".to_string()],
+                    ..validate_args(&file)
+                },
+                Verbosity::Normal,
+            );
+            assert_eq!(result.is_ok(), expected_ok);
+            png.append_chunk(Chunk::new(
+                ChunkType::from_str("ruSt").unwrap(),
+                b"hi".to_vec(),
+            ));
+        }
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_decode_all_with_key() {
+        let key = "secret";
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("abCd").unwrap(),
+            vec![0, 1, 2, 255],
+        ));
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            cipher::xor_encode(b"hidden message", key),
+        ));
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("efGh").unwrap(),
+            b"plain text".to_vec(),
+        ));
+
+        let messages = decode_all_with_key(&png, key);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].0.chunk_type().to_string(), "ruSt");
+        assert_eq!(messages[0].1, "hidden message");
+    }
+
+    #[test]
+    fn test_count_summary_matches_rows() {
+        let mut png = testing_png();
+        for message in ["a", "bc"] {
+            png.append_chunk(Chunk::new(
+                ChunkType::from_str("ruSt").unwrap(),
+                message.into(),
+            ));
+        }
+
+        let rows = chunk_histogram(&png);
+        assert_eq!(rows.len(), 4);
+        assert!(rows.contains(&("ruSt".to_string(), 2, 3)));
+        assert_eq!(histogram_total(&rows), (png.chunks().len(), 13 + 5 + 3));
+    }
+
+    #[test]
+    fn test_count_sort_by_size_desc() {
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            vec![0; 20],
+        ));
+
+        let mut rows = chunk_histogram(&png);
+        sort_histogram(&mut rows, SortKey::Size, true);
+        let types: Vec<&str> = rows.iter().map(|row| row.0.as_str()).collect();
+        assert_eq!(types, ["ruSt", "IHDR", "IDAT", "IEND"]);
+
+        sort_histogram(&mut rows, SortKey::Type, false);
+        assert_eq!(rows[0].0, "IDAT");
+    }
+
+    #[test]
+    fn test_split_concatenated_pngs() {
+        let file = temp_path("split.png");
+        let mut second = testing_png();
+        second.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"second".to_vec(),
+        ));
+        let mut bytes = testing_png().as_bytes();
+        bytes.extend_from_slice(&second.as_bytes());
+        fs::write(&file, &bytes).unwrap();
+
+        split(SplitArgs {
+            file: file.clone(),
+            output_template: "{dir}/{name}_{index}.{ext}".to_string(),
+        })
+        .unwrap();
+
+        let first_file = temp_path("split_0.png");
+        let second_file = temp_path("split_1.png");
+        assert_eq!(fs::read(&first_file).unwrap(), testing_png().as_bytes());
+        assert_eq!(fs::read(&second_file).unwrap(), second.as_bytes());
+        for path in [file, first_file, second_file] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_trailer_strip() {
+        let file = temp_path("trailer_strip.png");
+        let mut bytes = testing_png().as_bytes();
+        bytes.extend_from_slice(b"appended");
+        fs::write(&file, &bytes).unwrap();
+
+        let outcome = trailer(TrailerArgs {
+            file: file.clone(),
+            offset: None,
+            strip: false,
+            output: None,
+            in_place: false,
+        })
+        .unwrap();
+        assert_eq!(
+            outcome,
+            TrailerOutcome::Found {
+                file: file.clone(),
+                bytes: 8
+            }
+        );
+        assert_eq!(fs::read(&file).unwrap(), bytes);
+
+        let outcome = trailer(TrailerArgs {
+            file: file.clone(),
+            offset: None,
+            strip: true,
+            output: None,
+            in_place: true,
+        })
+        .unwrap();
+        assert_eq!(outcome, TrailerOutcome::Stripped(8));
+        assert_eq!(fs::read(&file).unwrap(), testing_png().as_bytes());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_list_lines() {
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"hi".to_vec(),
+        ));
+
+        let lines = list_lines(&png, None);
+        assert_eq!(lines.len(), 4);
+        let crc = png.chunks()[0].crc();
+        assert_eq!(
+            lines[0],
+            format!("    0  IHDR          13  {:08x}  CPR-", crc)
+        );
+
+        let lines = list_lines(&png, Some("ruSt"));
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("    2  ruSt           2  "));
+        assert!(lines[0].ends_with("--RS"));
+    }
+
+    #[test]
+    fn test_list_json() {
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"hi".to_vec(),
+        ));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&list_json(&png, None).unwrap()).unwrap();
+        let chunks = json.as_array().unwrap();
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks[0]["type"], "IHDR");
+        assert_eq!(chunks[0]["length"], 13);
+        assert_eq!(chunks[0]["critical"], true);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&list_json(&png, Some("ruSt")).unwrap()).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["crc"], png.chunks()[2].crc());
+        assert_eq!(json[0]["public"], false);
+        assert_eq!(json[0]["safe_to_copy"], true);
+    }
+
+    #[test]
+    fn test_repair_rewrites_bad_crcs() {
+        let file = temp_path("repair.png");
+        let mut bytes = testing_png().as_bytes();
+        let iend_crc = bytes.len() - 1;
+        let ihdr_crc = Png::HEADER_LENGHT + Chunk::MIN_BYTES + 13 - 1;
+        bytes[iend_crc] ^= 0xff;
+        bytes[ihdr_crc] ^= 0xff;
+        fs::write(&file, &bytes).unwrap();
+        assert_eq!(Png::collect_crc_errors(&bytes).unwrap().len(), 2);
+
+        let outcome = repair(
+            RepairArgs {
+                file: file.clone(),
+                report: true,
+                output: None,
+                in_place: true,
+            },
+            Verbosity::Normal,
+        )
+        .unwrap();
+        assert_eq!(outcome.crc_errors.len(), 2);
+        assert!(outcome.to_string().ends_with("\n2 CRC(s) fixed"));
+        assert!(Png::from_file(&file).is_ok());
+        assert_eq!(fs::read(&file).unwrap(), testing_png().as_bytes());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_detect_scores_encoded_file_higher() {
+        let clean = testing_png();
+        let file = temp_path("detect.png");
+        fs::write(&file, clean.as_bytes()).unwrap();
+        encode(encode_args(&file, "hidden"), Verbosity::Normal).unwrap();
+        let encoded = Png::from_file(&file).unwrap();
+
+        assert_eq!(pngme_score(&clean), 0);
+        assert!(pngme_score(&encoded) > pngme_score(&clean));
+        let outcome = detect(DetectArgs {
+            file: file.clone(),
+            offset: None,
+        })
+        .unwrap();
+        assert_eq!(outcome.score, pngme_score(&encoded));
+        assert_ne!(outcome.confidence(), "none");
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_exif_extracts_bytes() {
+        let exif_data: Vec<u8> = b"MM\0\x2a\0\0\0\x08\0\0".to_vec();
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("eXIf").unwrap(),
+            exif_data.clone(),
+        ));
+
+        let file = temp_path("exif.png");
+        let output = temp_path("exif.bin");
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let outcome = exif(ExifArgs {
+            file: file.clone(),
+            offset: None,
+            output: None,
+        })
+        .unwrap();
+        assert_eq!(
+            outcome,
+            ExifOutcome::Summary {
+                bytes: exif_data.len(),
+                byte_order: "big-endian"
+            }
+        );
+        let outcome = exif(ExifArgs {
+            file: file.clone(),
+            offset: None,
+            output: Some(output.clone()),
+        })
+        .unwrap();
+        assert!(outcome.is_status());
+
+        assert_eq!(fs::read(&output).unwrap(), exif_data);
+        fs::remove_file(file).unwrap();
+        fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_inspection_outcomes() {
+        let mut png = testing_png();
+        png.append_chunk(Chunk::from_type_and_str("ruSt", "hidden").unwrap());
+        let file = temp_path("inspection_outcomes.png");
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let outcome = count(CountArgs {
+            file: file.clone(),
+            offset: None,
+            summary: true,
+            sort_by: None,
+            desc: false,
+        })
+        .unwrap();
+        assert_eq!(outcome.rows.len(), 4);
+        assert_eq!(outcome.total, Some((4, 13 + 5 + 6)));
+        assert!(outcome.to_string().ends_with("\ntotal\t4\t24"));
+
+        let outcome = info(InfoArgs {
+            file: file.clone(),
+            offset: None,
+        })
+        .unwrap();
+        assert_eq!((outcome.header.width, outcome.header.height), (1, 1));
+        assert_eq!(outcome.palette, None);
+        assert_eq!(outcome.chunks[2], ("ruSt".to_string(), 1));
+
+        let outcome = list(ListArgs {
+            file: file.clone(),
+            offset: None,
+            chunk_type: Some("ruSt".to_string()),
+            json: false,
+            no_validate_type: false,
+        })
+        .unwrap();
+        assert_eq!(outcome, ListOutcome::Table(list_lines(&png, Some("ruSt"))));
+
+        let outcome = capacity(CapacityArgs { file: file.clone() }, Verbosity::Normal).unwrap();
+        assert_eq!(outcome.capacity, png.total_capacity());
+
+        let outcome = diff(
+            DiffArgs {
+                before: file.clone(),
+                after: file.clone(),
+            },
+            Verbosity::Normal,
+        )
+        .unwrap();
+        assert!(outcome.lines.is_empty());
+
+        let outcome = fingerprint(FingerprintArgs {
+            file: file.clone(),
+            offset: None,
+            hash: HashAlgorithm::Sha256,
+        })
+        .unwrap();
+        assert_eq!(
+            outcome.digest,
+            image_fingerprint(&png, HashAlgorithm::Sha256).unwrap()
+        );
+        fs::remove_file(file).unwrap();
+    }
+}
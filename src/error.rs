@@ -0,0 +1,123 @@
+use std::fmt;
+use std::io;
+use std::string::FromUtf8Error;
+
+use crate::chunk::ChunkError;
+use crate::chunk_type::ChunkTypeError;
+use crate::cipher::CipherError;
+use crate::png::PngError;
+
+/// Every way an operation of this crate can fail, so callers can match on the kind
+/// of failure
+#[derive(Debug)]
+pub enum PngMeError {
+    Chunk(ChunkError),
+    ChunkType(ChunkTypeError),
+    Png(PngError),
+    Cipher(CipherError),
+    Io(io::Error),
+    Utf8(FromUtf8Error),
+}
+
+impl std::error::Error for PngMeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PngMeError::Chunk(err) => Some(err),
+            PngMeError::ChunkType(err) => Some(err),
+            PngMeError::Png(err) => Some(err),
+            PngMeError::Cipher(err) => Some(err),
+            PngMeError::Io(err) => Some(err),
+            PngMeError::Utf8(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for PngMeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PngMeError::Chunk(err) => write!(f, "{}", err),
+            PngMeError::ChunkType(err) => write!(f, "{}", err),
+            PngMeError::Png(err) => write!(f, "{}", err),
+            PngMeError::Cipher(err) => write!(f, "{}", err),
+            PngMeError::Io(err) => write!(f, "{}", err),
+            PngMeError::Utf8(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<ChunkError> for PngMeError {
+    fn from(err: ChunkError) -> Self {
+        PngMeError::Chunk(err)
+    }
+}
+
+impl From<ChunkTypeError> for PngMeError {
+    fn from(err: ChunkTypeError) -> Self {
+        PngMeError::ChunkType(err)
+    }
+}
+
+impl From<PngError> for PngMeError {
+    fn from(err: PngError) -> Self {
+        PngMeError::Png(err)
+    }
+}
+
+impl From<CipherError> for PngMeError {
+    fn from(err: CipherError) -> Self {
+        PngMeError::Cipher(err)
+    }
+}
+
+impl From<io::Error> for PngMeError {
+    fn from(err: io::Error) -> Self {
+        PngMeError::Io(err)
+    }
+}
+
+impl From<FromUtf8Error> for PngMeError {
+    fn from(err: FromUtf8Error) -> Self {
+        PngMeError::Utf8(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use crate::png::Png;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_match_on_error_kind() {
+        let err = Png::try_from(&[137, 80, 78][..]).unwrap_err();
+        assert!(matches!(err, PngMeError::Png(PngError::TooSmall)));
+
+        let err = ChunkType::from_str("Ru1t").unwrap_err();
+        assert!(matches!(
+            err,
+            PngMeError::ChunkType(ChunkTypeError::InvalidCharacter)
+        ));
+
+        let err = Png::from_file("does/not/exist.png").unwrap_err();
+        assert!(matches!(err, PngMeError::Png(PngError::FileNotFound(_))));
+    }
+
+    #[test]
+    fn test_source_is_the_wrapped_error() {
+        use std::error::Error;
+
+        let err = PngMeError::from(io::Error::other("disk"));
+        let source = err.source().unwrap();
+        assert_eq!(
+            source.downcast_ref::<io::Error>().unwrap().to_string(),
+            "disk"
+        );
+
+        let err = Png::try_from(&[137, 80, 78][..]).unwrap_err();
+        assert!(matches!(
+            err.source().unwrap().downcast_ref::<PngError>(),
+            Some(PngError::TooSmall)
+        ));
+    }
+}
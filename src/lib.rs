@@ -0,0 +1,20 @@
+//! Library behind the `pngme` command line program.
+//!
+//! The public API is made of:
+//! - [`png::Png`], a parsed PNG file that can be read, modified and serialized
+//! - [`chunk::Chunk`], a single PNG chunk with its data and CRC
+//! - [`chunk_type::ChunkType`], a validated four letter chunk type
+//! - [`cipher`], the XOR helpers used to obfuscate hidden messages
+//! - [`PngMeError`], aliased as [`Error`], and the [`Result`] returned by fallible
+//!   operations
+
+pub mod chunk;
+pub mod chunk_type;
+pub mod cipher;
+pub mod error;
+pub mod png;
+
+pub use error::PngMeError;
+
+pub type Error = PngMeError;
+pub type Result<T> = std::result::Result<T, Error>;
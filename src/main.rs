@@ -1,26 +1,83 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::process;
+
 use clap::Parser;
 
 mod args;
-mod chunk;
-mod chunk_type;
-mod cipher;
+mod audit;
 mod commands;
-mod png;
 
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Error>;
+/// The commands fail with the errors of the library as well as their own
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Prints the outcome of a command that returns one, unless `verbosity` is quiet
+fn print<T: fmt::Display>(verbosity: args::Verbosity) -> impl Fn(T) {
+    move |outcome| commands::report(outcome, verbosity)
+}
+
+/// Prints the outcome of a command whose output is the data asked for, whatever the verbosity
+fn show<T: fmt::Display>(outcome: T) {
+    println!("{}", outcome)
+}
+
+/// Prints an outcome that is only a status like `print`, and any other like `show`
+fn print_or_show<T: fmt::Display>(status: bool, outcome: T, verbosity: args::Verbosity) {
+    if status {
+        print(verbosity)(outcome)
+    } else {
+        show(outcome)
+    }
+}
 
-fn main() -> Result<()> {
+fn main() {
     let cli = args::Cli::parse();
+    let verbosity = cli.verbosity();
     match cli.command {
-        args::PngMeArgs::Encode(args) => commands::encode(args),
-        args::PngMeArgs::Decode(args) => commands::decode(args),
-        args::PngMeArgs::Find(args) => commands::find(args),
-        args::PngMeArgs::Remove(args) => commands::remove(args),
-        args::PngMeArgs::Print(args) => commands::print_chunks(args),
+        args::PngMeArgs::Encode(args) => commands::encode(args, verbosity).map(|outcome| {
+            commands::warn(outcome.warning(), verbosity);
+            print(verbosity)(outcome)
+        }),
+        args::PngMeArgs::Decode(args) => commands::decode(args, verbosity)
+            .map(|outcome| print_or_show(outcome.is_status(), outcome, verbosity)),
+        args::PngMeArgs::Find(args) => commands::find(args, verbosity).map(print(verbosity)),
+        args::PngMeArgs::Remove(args) => commands::remove(args, verbosity).map(print(verbosity)),
+        args::PngMeArgs::Print(args) if args.jsonl => {
+            commands::print_jsonl(args, io::stdout().lock())
+        }
+        args::PngMeArgs::Print(args) => commands::print_chunks(args).map(show),
+        args::PngMeArgs::Dedup(args) => commands::dedup(args).map(print(verbosity)),
+        args::PngMeArgs::Exif(args) => commands::exif(args)
+            .map(|outcome| print_or_show(outcome.is_status(), outcome, verbosity)),
+        args::PngMeArgs::Extract(args) => commands::extract(args, verbosity).map(|outcome| {
+            commands::warn(outcome.warning(), verbosity);
+            print(verbosity)(outcome)
+        }),
+        args::PngMeArgs::Fingerprint(args) => commands::fingerprint(args).map(show),
+        args::PngMeArgs::Validate(args) => {
+            commands::validate(args, verbosity).map(print(verbosity))
+        }
+        args::PngMeArgs::Namespace(args) => commands::namespace(args).map(print(verbosity)),
+        args::PngMeArgs::Detect(args) => commands::detect(args).map(show),
+        args::PngMeArgs::Count(args) => commands::count(args).map(show),
+        args::PngMeArgs::Trailer(args) => commands::trailer(args)
+            .map(|outcome| print_or_show(outcome.is_status(), outcome, verbosity)),
+        args::PngMeArgs::Info(args) => commands::info(args).map(show),
+        args::PngMeArgs::List(args) => commands::list(args).map(show),
+        args::PngMeArgs::Repair(args) => commands::repair(args, verbosity).map(print(verbosity)),
+        args::PngMeArgs::Split(args) => commands::split(args).map(print(verbosity)),
+        args::PngMeArgs::Replace(args) => commands::replace(args).map(print(verbosity)),
+        args::PngMeArgs::Rekey(args) => commands::rekey(args).map(print(verbosity)),
+        args::PngMeArgs::Capacity(args) => {
+            commands::capacity(args, verbosity).map(print(verbosity))
+        }
+        args::PngMeArgs::Diff(args) => commands::diff(args, verbosity).map(print(verbosity)),
+        args::PngMeArgs::Completions(args) => io::stdout()
+            .write_all(&commands::completions(args))
+            .map_err(Into::into),
     }
     .unwrap_or_else(|err| {
         eprintln!("Error: {}", err);
+        process::exit(1);
     });
-    Ok(())
 }
@@ -1,38 +1,407 @@
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
 
-use crate::chunk::Chunk;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::chunk::{Chunk, ChunkError, CRC_ALGORITHM};
+use crate::chunk_type::ChunkType;
 use crate::{Error, Result};
 
+/// Bit that tells apart uppercase and lowercase ASCII letters
+const CASE_BIT: u8 = 1 << 5;
+
+/// Returns true when the chunk types, in file order, follow a rule
+type OrderRule = fn(&[&str]) -> bool;
+
+/// Chunk ordering rules of the PNG spec checked by `Png::check_order`, in order,
+/// each with the description reported when the chunk types break it
+const ORDER_RULES: [(&str, OrderRule); 6] = [
+    ("IHDR must be the first Chunk", |types| {
+        types.first() == Some(&"IHDR")
+    }),
+    ("PLTE must come before the first IDAT", |types| {
+        before_idat(types, "PLTE")
+    }),
+    ("tRNS must come before the first IDAT", |types| {
+        before_idat(types, "tRNS")
+    }),
+    ("bKGD must come before the first IDAT", |types| {
+        before_idat(types, "bKGD")
+    }),
+    ("IDAT Chunks must be contiguous", |types| {
+        let first = types.iter().position(|&t| t == "IDAT");
+        let last = types.iter().rposition(|&t| t == "IDAT");
+        match (first, last) {
+            (Some(first), Some(last)) => types[first..=last].iter().all(|&t| t == "IDAT"),
+            _ => true,
+        }
+    }),
+    ("IEND must be the last Chunk", |types| {
+        types.last() == Some(&"IEND")
+    }),
+];
+
+/// True when every chunk of `chunk_type` comes before the first IDAT chunk
+fn before_idat(types: &[&str], chunk_type: &str) -> bool {
+    let first_idat = types
+        .iter()
+        .position(|&t| t == "IDAT")
+        .unwrap_or(types.len());
+    types[first_idat..].iter().all(|&t| t != chunk_type)
+}
+
 /// A PNG container as described by the PNG spec
 /// http://www.libpng.org/pub/png/spec/1.2/PNG-Contents.html
 #[derive(Debug)]
 pub struct Png {
     chunks: Vec<Chunk>,
+    trailer: Vec<u8>,
+    gzipped: bool,
 }
 
 impl Png {
     /// The STANDARD_HEADER size
     pub const HEADER_LENGHT: usize = 8;
+    /// The first two bytes of a gzip stream
+    pub const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
     /// The first eight bytes of a PNG file always contain this values
     pub const STANDARD_HEADER: [u8; Png::HEADER_LENGHT] = [137, 80, 78, 71, 13, 10, 26, 10];
+    /// Chunk types registered by the PNG spec and its official extensions
+    pub const STANDARD_CHUNK_TYPES: [&'static str; 31] = [
+        "IHDR", "PLTE", "IDAT", "IEND", "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "cICP", "mDCv",
+        "cLLi", "bKGD", "hIST", "tRNS", "eXIf", "pHYs", "sPLT", "tIME", "iTXt", "tEXt", "zTXt",
+        "acTL", "fcTL", "fdAT", "oFFs", "pCAL", "sCAL", "gIFg", "gIFx", "sTER",
+    ];
 
     /// Creates a `Png` from a list of chunks using the STANDARD_HEADER
     pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
-        Self { chunks }
+        Self {
+            chunks,
+            trailer: Vec::new(),
+            gzipped: false,
+        }
     }
 
-    /// Creates a `Png` from a file path
+    /// Creates a `Png` from a file path, streaming it through `from_reader` so the
+    /// whole file is never held in memory at once. Any data after IEND is kept.
+    /// Symlinks are followed, a missing file and a dangling symlink are reported
+    /// as distinct errors. A gzip-compressed file (e.g. `.png.gz`) is decompressed
+    /// on the fly and remembered, see `is_gzipped`.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let bytes: Vec<u8> = fs::read(path)?;
-        Ok(Self::try_from(bytes.as_slice())?)
+        let path = path.as_ref();
+        let file = fs::File::open(path).map_err(|err| Self::open_error(path, err))?;
+        let mut reader = BufReader::new(file);
+        if !reader.fill_buf()?.starts_with(&Self::GZIP_MAGIC) {
+            return Self::from_reader(reader);
+        }
+
+        let mut png = Self::from_reader(GzDecoder::new(reader))?;
+        png.gzipped = true;
+        Ok(png)
+    }
+
+    /// Tells apart a missing file and a dangling symlink when `path` can't be opened
+    fn open_error(path: &Path, err: io::Error) -> Error {
+        if err.kind() != io::ErrorKind::NotFound {
+            return err.into();
+        }
+        match fs::symlink_metadata(path) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                PngError::BrokenSymlink(path.to_path_buf()).into()
+            }
+            _ => PngError::FileNotFound(path.to_path_buf()).into(),
+        }
+    }
+
+    /// Creates a `Png` from a file path like `from_file`, reading the whole file at once.
+    /// Any data after IEND is kept, see `trailing_bytes`.
+    pub fn from_file_with_trailer<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_file_with(path, Self::from_bytes_with_trailer)
+    }
+
+    /// Reads the file at `path` like `from_file`, but builds the `Png` from its bytes
+    /// with `parse`, e.g. `Png::from_bytes_raw_types`
+    pub fn from_file_with<P: AsRef<Path>>(
+        path: P,
+        parse: impl FnOnce(&[u8]) -> Result<Self>,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes: Vec<u8> = fs::read(path).map_err(|err| Self::open_error(path, err))?;
+        if !bytes.starts_with(&Self::GZIP_MAGIC) {
+            return parse(bytes.as_slice());
+        }
+
+        let mut decompressed: Vec<u8> = Vec::new();
+        GzDecoder::new(bytes.as_slice()).read_to_end(&mut decompressed)?;
+        let mut png = parse(decompressed.as_slice())?;
+        png.gzipped = true;
+        Ok(png)
+    }
+
+    /// Creates a `Png` from the bytes of a whole PNG file.
+    /// Fails with `PngError::MissingIend` if the last chunk isn't IEND and with
+    /// `PngError::TrailingData` if non-zero bytes follow it.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::try_from(bytes)
+    }
+
+    /// Creates one `Png` for each PNG file concatenated in `bytes`, a new one starting
+    /// wherever the data after an IEND begins with the PNG signature.
+    pub fn from_bytes_multi(bytes: &[u8]) -> Result<Vec<Self>> {
+        let mut pngs: Vec<Self> = vec![Self::parse(bytes, None, true)?];
+        while let Some(last) = pngs.last_mut() {
+            last.check_iend()?;
+            if !last.trailer.starts_with(&Self::STANDARD_HEADER) {
+                last.check_trailer()?;
+                break;
+            }
+            let next = Self::parse(&last.strip_trailing_bytes(), None, true)?;
+            pngs.push(next);
+        }
+        Ok(pngs)
+    }
+
+    /// Creates a `Png` from a PNG file embedded in `bytes` at `offset`, e.g. carved out
+    /// of a disk image. The signature must start at `offset`. Whatever follows IEND
+    /// belongs to the container, so it's ignored.
+    pub fn from_bytes_embedded(bytes: &[u8], offset: usize) -> Result<Self> {
+        let bytes = bytes.get(offset..).ok_or(PngError::TooSmall)?;
+        let mut png = Self::parse(bytes, None, true)?;
+        png.check_iend()?;
+        png.trailer.clear();
+        Ok(png)
+    }
+
+    /// Creates a `Png` from the bytes of a whole PNG file like `from_bytes`, but keeps
+    /// any data after IEND instead of failing, see `trailing_bytes`.
+    pub fn from_bytes_with_trailer(bytes: &[u8]) -> Result<Self> {
+        let png = Self::parse(bytes, None, true)?;
+        png.check_iend()?;
+        Ok(png)
+    }
+
+    /// Creates a `Png` from the bytes of a whole PNG file like `from_bytes_with_trailer`,
+    /// but accepts any four bytes as a chunk type, see `ChunkType::from_bytes_unchecked`.
+    pub fn from_bytes_raw_types(bytes: &[u8]) -> Result<Self> {
+        let png = Self::parse(bytes, None, false)?;
+        png.check_iend()?;
+        Ok(png)
+    }
+
+    /// Creates a `Png` from the bytes of a whole PNG file like `from_bytes`, but chunks
+    /// whose stored CRC is wrong are kept, with a correct CRC, and reported instead of
+    /// failing the whole parse.
+    pub fn from_bytes_lenient(bytes: &[u8]) -> Result<(Self, Vec<CrcMismatch>)> {
+        let mut crc_errors: Vec<CrcMismatch> = Vec::new();
+        let png = Self::parse(bytes, Some(&mut crc_errors), true)?;
+        Ok((png, crc_errors))
+    }
+
+    /// Creates a `Png` from the bytes of a PNG file like `from_bytes_lenient`, but a file
+    /// cut short, e.g. by an interrupted download, is salvaged instead of failing: the
+    /// complete chunks are kept and IEND is added back when missing. Also returns the
+    /// number of bytes of the truncated chunk that were dropped.
+    pub fn from_bytes_salvaged(bytes: &[u8]) -> Result<(Self, Vec<CrcMismatch>, usize)> {
+        let (mut png, crc_errors, dropped) = match Self::from_bytes_lenient(bytes) {
+            Err(Error::Png(PngError::Truncated(_, offset))) => {
+                let (png, crc_errors) = Self::from_bytes_lenient(&bytes[..offset])?;
+                (png, crc_errors, bytes.len() - offset)
+            }
+            result => {
+                let (png, crc_errors) = result?;
+                (png, crc_errors, 0)
+            }
+        };
+        if !png.ends_with_iend() {
+            png.append_chunk(Chunk::new(ChunkType::try_from(*b"IEND")?, Vec::new()));
+        }
+        Ok((png, crc_errors, dropped))
+    }
+
+    /// Lists every chunk of a PNG file whose stored CRC doesn't match its content
+    pub fn collect_crc_errors(bytes: &[u8]) -> Result<Vec<CrcMismatch>> {
+        Ok(Self::from_bytes_lenient(bytes)?.1)
+    }
+
+    /// Creates a `Png` by reading the signature and then one chunk at a time
+    /// from `reader` until IEND, e.g. from stdin. Fails with `PngError::MissingIend`
+    /// if the stream ends first. Any data after IEND is kept, see `trailing_bytes`.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
+        Self::from_reader_at(reader, 0)
+    }
+
+    /// Creates a `Png` from `reader` starting at byte `offset`, which must be a chunk
+    /// boundary. Everything before `offset` is skipped, so only the chunks from there
+    /// on are returned. An `offset` of 0 parses the whole stream including the signature.
+    pub fn from_reader_at<R: Read>(reader: R, offset: u64) -> Result<Self> {
+        Self::read_stream(reader, offset, |_| {})
+    }
+
+    /// Creates a `Png` from `reader` like `from_reader`, calling `progress` with the
+    /// number of bytes read so far after each chunk, e.g. to show a progress bar
+    pub fn from_reader_with_progress<R: Read, F: FnMut(u64)>(
+        reader: R,
+        progress: F,
+    ) -> Result<Self> {
+        Self::read_stream(reader, 0, progress)
+    }
+
+    /// Reads the chunks from `offset` on, see `from_reader_at`, reporting the bytes
+    /// read after each one to `progress`
+    fn read_stream<R: Read, F: FnMut(u64)>(
+        reader: R,
+        offset: u64,
+        mut progress: F,
+    ) -> Result<Self> {
+        let mut stream = ChunkStream::new(reader, offset)?;
+        let mut chunks: Vec<Chunk> = Vec::new();
+        while let Some(chunk) = stream.next().transpose()? {
+            progress(stream.position());
+            chunks.push(chunk);
+        }
+
+        let mut png = Self::from_chunks(chunks);
+        png.check_iend()?;
+        stream.reader.read_to_end(&mut png.trailer)?;
+        Ok(png)
+    }
+
+    /// Reads the chunks of the PNG from `reader` one at a time as they are iterated,
+    /// without keeping any of them, so even files larger than memory can be scanned.
+    /// The signature is checked right away, the chunks stop after IEND.
+    pub fn stream_chunks<R: Read>(reader: R) -> Result<ChunkStream<R>> {
+        ChunkStream::new(reader, 0)
+    }
+
+    /// Streams the chunks from `reader` like `stream_chunks`, starting at byte `offset`
+    /// like `from_reader_at`
+    pub fn stream_chunks_at<R: Read>(reader: R, offset: u64) -> Result<ChunkStream<R>> {
+        ChunkStream::new(reader, offset)
+    }
+
+    /// Streams the chunks from `reader` like `stream_chunks`, yielding only those
+    /// that could have a message as they are read. They come in file order since
+    /// ranking them needs them all, see `find_possible_messages`.
+    pub fn stream_possible_messages<R: Read>(
+        reader: R,
+    ) -> Result<impl Iterator<Item = Result<Chunk>>> {
+        Ok(Self::stream_chunks(reader)?
+            .filter(|chunk| !matches!(chunk, Ok(chunk) if PossibleMessage::new(chunk).is_none())))
+    }
+
+    /// Checks that `bytes` start with the `STANDARD_HEADER` signature
+    pub fn check_signature(bytes: &[u8]) -> Result<()> {
+        let header = &bytes[..bytes.len().min(Self::HEADER_LENGHT)];
+        if !Self::STANDARD_HEADER.starts_with(header) {
+            return Err(PngError::InvalidSignature(header.to_vec()).into());
+        }
+        if header.len() < Self::HEADER_LENGHT {
+            return Err(PngError::TooSmall.into());
+        }
+        Ok(())
+    }
+
+    /// Reads the next chunk from `reader`, returns `None` once the reader is exhausted.
+    /// `chunks_read` and `offset` only describe where the stream is for error reporting.
+    fn read_chunk<R: Read>(
+        reader: &mut R,
+        chunks_read: usize,
+        offset: usize,
+    ) -> Result<Option<Chunk>> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(Chunk::MIN_BYTES);
+        reader
+            .take(Chunk::LENGTH_BYTES as u64)
+            .read_to_end(&mut bytes)?;
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+        let truncated = || PngError::Truncated(chunks_read, offset);
+        let size: [u8; Chunk::LENGTH_BYTES] =
+            bytes.as_slice().try_into().map_err(|_| truncated())?;
+
+        let remaining =
+            Chunk::CHUNK_TYPE_BYTES + u32::from_be_bytes(size) as usize + Chunk::CRC_BYTES;
+        reader.take(remaining as u64).read_to_end(&mut bytes)?;
+        if bytes.len() < Chunk::LENGTH_BYTES + remaining {
+            return Err(truncated().into());
+        }
+
+        Ok(Some(Chunk::try_from(bytes.as_slice())?))
     }
 
-    /// Appends a chunk to the end of this `Png` file's `Chunk` list.
+    /// Appends a chunk to this `Png` file's `Chunk` list. The chunk is inserted right
+    /// before a trailing IEND so IEND stays last, otherwise it goes at the end.
     pub fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk)
+        let before_end = chunk.chunk_type().to_string() != "IEND"
+            && self
+                .chunks
+                .last()
+                .is_some_and(|last| last.chunk_type().to_string() == "IEND");
+        if before_end {
+            self.chunks.insert(self.chunks.len() - 1, chunk);
+        } else {
+            self.chunks.push(chunk)
+        }
+    }
+
+    /// Inserts a chunk at `index`, shifting the chunks after it. An `index` equal to the
+    /// number of chunks puts it at the end. IHDR stays first and IEND last, so an
+    /// `index` before IHDR or past IEND is refused.
+    pub fn insert_chunk(&mut self, index: usize, chunk: Chunk) -> Result<()> {
+        if index > self.chunks.len() {
+            return Err(PngError::IndexOutOfRange(index, self.chunks.len()).into());
+        }
+        let type_at = |chunk: Option<&Chunk>| chunk.map(|chunk| chunk.chunk_type().to_string());
+        if index == 0 && type_at(self.chunks.first()).as_deref() == Some("IHDR") {
+            return Err(PngError::ImmovableChunk("IHDR".to_string()).into());
+        }
+        if index == self.chunks.len() && type_at(self.chunks.last()).as_deref() == Some("IEND") {
+            return Err(PngError::ImmovableChunk("IEND".to_string()).into());
+        }
+        self.chunks.insert(index, chunk);
+        Ok(())
+    }
+
+    /// Inserts a chunk right before the acTL chunk of an animated PNG, where it
+    /// precedes every frame and can't break the order of the animation chunks.
+    /// Files without acTL aren't animated, so the chunk is appended like `append_chunk`.
+    pub fn insert_before_animation(&mut self, chunk: Chunk) {
+        match self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type().to_string() == "acTL")
+        {
+            Some(index) => self.chunks.insert(index, chunk),
+            None => self.append_chunk(chunk),
+        }
+    }
+
+    /// Replaces every chunk of this `Png` with `chunks`, which must start with IHDR
+    /// and end with IEND. The chunks are left untouched when they don't.
+    pub fn set_chunks(&mut self, chunks: Vec<Chunk>) -> Result<()> {
+        let type_at = |chunk: Option<&Chunk>| chunk.map(|chunk| chunk.chunk_type().to_string());
+        if type_at(chunks.first()).as_deref() != Some("IHDR") {
+            return Err(PngError::MisplacedChunk("IHDR".to_string()).into());
+        }
+        if type_at(chunks.last()).as_deref() != Some("IEND") {
+            return Err(PngError::MisplacedChunk("IEND".to_string()).into());
+        }
+        self.chunks = chunks;
+        Ok(())
+    }
+
+    /// Keeps only the chunks for which `f` returns true, in their order.
+    /// IHDR and IEND are always kept so the result is still a valid PNG.
+    pub fn retain<F: FnMut(&Chunk) -> bool>(&mut self, mut f: F) {
+        self.chunks.retain(|chunk| {
+            matches!(chunk.chunk_type().to_string().as_str(), "IHDR" | "IEND") || f(chunk)
+        });
     }
 
     /// Searches for a `Chunk` with the specified `chunk_type` and removes the first
@@ -48,6 +417,164 @@ impl Png {
         Ok(removed)
     }
 
+    /// Removes ancillary chunks whose type and data duplicate another chunk's.
+    /// The first occurrence is kept unless `keep_last` is set, in which case the
+    /// last occurrence survives. Returns the number of chunks removed.
+    pub fn dedup(&mut self, keep_last: bool) -> usize {
+        let is_duplicate = |a: &Chunk, b: &Chunk| {
+            !a.chunk_type().is_critical()
+                && a.chunk_type() == b.chunk_type()
+                && a.data() == b.data()
+        };
+
+        let before = self.chunks.len();
+        let mut kept: Vec<Chunk> = Vec::with_capacity(before);
+        if keep_last {
+            for chunk in self.chunks.drain(..).rev() {
+                if !kept.iter().any(|seen| is_duplicate(seen, &chunk)) {
+                    kept.push(chunk);
+                }
+            }
+            kept.reverse();
+        } else {
+            for chunk in self.chunks.drain(..) {
+                if !kept.iter().any(|seen| is_duplicate(seen, &chunk)) {
+                    kept.push(chunk);
+                }
+            }
+        }
+        self.chunks = kept;
+        before - self.chunks.len()
+    }
+
+    /// Changes the type of the chunk at `index`, recomputing its CRC.
+    pub fn rename_chunk(&mut self, index: usize, chunk_type: ChunkType) -> Result<()> {
+        let len = self.chunks.len();
+        let chunk = self
+            .chunks
+            .get_mut(index)
+            .ok_or(PngError::IndexOutOfRange(index, len))?;
+
+        *chunk = Chunk::new(chunk_type, chunk.data().to_vec());
+        Ok(())
+    }
+
+    /// Appends `data` to the data of the chunk at `index`, recomputing its CRC.
+    pub fn extend_chunk_data(&mut self, index: usize, data: &[u8]) -> Result<()> {
+        let len = self.chunks.len();
+        let chunk = self
+            .chunks
+            .get_mut(index)
+            .ok_or(PngError::IndexOutOfRange(index, len))?;
+
+        let mut extended = chunk.data().to_vec();
+        extended.extend_from_slice(data);
+        *chunk = Chunk::new(chunk.chunk_type().clone(), extended);
+        Ok(())
+    }
+
+    /// Replaces the chunk at position `index` with `chunk` and returns the old one
+    pub fn replace_chunk(&mut self, index: usize, chunk: Chunk) -> Result<Chunk> {
+        let len = self.chunks.len();
+        let old = self
+            .chunks
+            .get_mut(index)
+            .ok_or(PngError::IndexOutOfRange(index, len))?;
+        Ok(std::mem::replace(old, chunk))
+    }
+
+    /// Replaces the data of the first chunk with type `chunk_type` by `data`, keeping
+    /// its position in the chunk order. Fails if there is no such chunk.
+    pub fn replace_chunk_data(&mut self, chunk_type: &str, data: Vec<u8>) -> Result<()> {
+        let index = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type().to_string() == chunk_type)
+            .ok_or(PngError::UnknownChunkType(chunk_type.to_string()))?;
+
+        let chunk = Chunk::new_checked(self.chunks[index].chunk_type().clone(), data)?;
+        self.replace_chunk(index, chunk)?;
+        Ok(())
+    }
+
+    /// Swaps the chunks at positions `i` and `j`. IHDR and IEND can't be moved
+    /// since they must stay the first and last chunks.
+    pub fn swap_chunks(&mut self, i: usize, j: usize) -> Result<()> {
+        for index in [i, j] {
+            let chunk = self
+                .chunks
+                .get(index)
+                .ok_or(PngError::IndexOutOfRange(index, self.chunks.len()))?;
+            let chunk_type = chunk.chunk_type().to_string();
+            if i != j && (chunk_type == "IHDR" || chunk_type == "IEND") {
+                return Err(PngError::ImmovableChunk(chunk_type).into());
+            }
+        }
+        self.chunks.swap(i, j);
+        Ok(())
+    }
+
+    /// Renames every ancillary non-standard chunk so its type starts with the two
+    /// letters of `prefix`. The case of the first two bytes is kept, so the critical
+    /// and public property bits don't change. Returns the number of renamed chunks.
+    /// Nothing is renamed if a new type would be a standard one, one already in the
+    /// file or the new type of another chunk type.
+    pub fn apply_namespace(&mut self, prefix: &str) -> Result<usize> {
+        let prefix: [u8; 2] = prefix
+            .as_bytes()
+            .try_into()
+            .map_err(|_| PngError::InvalidNamespace(prefix.to_string()))?;
+
+        let existing: HashSet<String> = self
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        let mut renamed_from: HashMap<String, String> = HashMap::new();
+        let mut renames: Vec<(usize, ChunkType)> = Vec::new();
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            let chunk_type = chunk.chunk_type();
+            if chunk_type.is_critical() || Self::is_standard_type(&chunk_type.to_string()) {
+                continue;
+            }
+
+            let mut bytes = chunk_type.bytes();
+            for (byte, letter) in bytes.iter_mut().zip(prefix) {
+                *byte = (letter & !CASE_BIT) | (*byte & CASE_BIT);
+            }
+            let new_type = ChunkType::try_from(bytes)?;
+            if &new_type == chunk_type {
+                continue;
+            }
+
+            let (old, new) = (chunk_type.to_string(), new_type.to_string());
+            let clashes = Self::is_standard_type(&new)
+                || existing.contains(&new)
+                || renamed_from.get(&new).is_some_and(|other| *other != old);
+            if clashes {
+                return Err(PngError::NamespaceCollision(old, new).into());
+            }
+            renamed_from.insert(new, old);
+            renames.push((index, new_type));
+        }
+
+        let renamed = renames.len();
+        for (index, new_type) in renames {
+            self.rename_chunk(index, new_type)?;
+        }
+        Ok(renamed)
+    }
+
+    /// Removes every `Chunk` with the specified `chunk_type` from this `Png` and
+    /// returns them in file order. Nothing is removed when there is no match.
+    pub fn remove_all_chunks(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        let (removed, kept): (Vec<Chunk>, Vec<Chunk>) = self
+            .chunks
+            .drain(..)
+            .partition(|chunk| chunk.chunk_type().to_string() == chunk_type);
+        self.chunks = kept;
+        removed
+    }
+
     /// The header of this PNG.
     pub fn header(&self) -> &[u8; 8] {
         &Self::STANDARD_HEADER
@@ -58,6 +585,11 @@ impl Png {
         &self.chunks
     }
 
+    /// Iterates over the `Chunk`s stored in this `Png` in file order
+    pub fn iter(&self) -> std::slice::Iter<'_, Chunk> {
+        self.chunks.iter()
+    }
+
     /// Searches for a `Chunk` with the specified `chunk_type` and returns the first
     /// matching `Chunk` from this `Png`.
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
@@ -66,29 +598,143 @@ impl Png {
             .find(|&chunk| chunk.chunk_type().to_string() == chunk_type)
     }
 
+    /// Returns every `Chunk` with the specified `chunk_type` in file order.
+    pub fn chunks_by_type(&self, chunk_type: &str) -> Vec<&Chunk> {
+        self.chunks
+            .iter()
+            .filter(|&chunk| chunk.chunk_type().to_string() == chunk_type)
+            .collect()
+    }
+
+    /// Counts the chunks in this `Png` with the specified `chunk_type`.
+    pub fn count_by_type(&self, chunk_type: &str) -> usize {
+        self.chunks
+            .iter()
+            .filter(|&chunk| chunk.chunk_type().to_string() == chunk_type)
+            .count()
+    }
+
     /// Returns this `Png` as a byte sequence.
     /// These bytes will contain the header followed by the bytes of all of the chunks.
     pub fn as_bytes(&self) -> Vec<u8> {
-        let chunks_bytes: Vec<u8> = self
+        let size: usize = self
             .chunks()
             .iter()
-            .flat_map(|chunk| chunk.as_bytes())
+            .map(|chunk| Chunk::MIN_BYTES + chunk.data().len())
+            .sum();
+
+        let mut bytes: Vec<u8> =
+            Vec::with_capacity(Self::HEADER_LENGHT + size + self.trailer.len());
+        bytes.extend_from_slice(self.header());
+        for chunk in self.chunks() {
+            chunk.as_bytes_into(&mut bytes);
+        }
+        bytes.extend_from_slice(&self.trailer);
+        bytes
+    }
+
+    /// Stores the data shared by several chunks only once, the later chunks referencing
+    /// the data of the first one. This only changes the memory used while processing,
+    /// the bytes of the file stay the same. Returns how many chunks now share data.
+    pub fn share_duplicate_data(&mut self) -> usize {
+        let mut first: HashMap<&[u8], usize> = HashMap::new();
+        let duplicates: Vec<(usize, usize)> = self
+            .chunks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, chunk)| match first.get(chunk.data()) {
+                Some(&original) => Some((original, index)),
+                None => {
+                    first.insert(chunk.data(), index);
+                    None
+                }
+            })
             .collect();
+        for &(original, duplicate) in duplicates.iter() {
+            let data = self.chunks[original].share();
+            self.chunks[duplicate].share_data(data);
+        }
+        duplicates.len()
+    }
 
-        self.header()
+    /// Returns how many bytes of chunk data are held in memory, counting the data
+    /// shared by several chunks once, see `share_duplicate_data`
+    pub fn data_bytes_in_memory(&self) -> usize {
+        let mut seen: HashSet<*const u8> = HashSet::new();
+        self.chunks
             .iter()
-            .chain(chunks_bytes.iter())
-            .copied()
-            .collect()
+            .map(Chunk::data)
+            .filter(|data| seen.insert(data.as_ptr()))
+            .map(|data| data.len())
+            .sum()
+    }
+
+    /// Returns a CRC-32 of the whole file as returned by `as_bytes`, signature and
+    /// trailing bytes included. Unlike the CRC of each chunk it changes whenever any
+    /// chunk changes, and unlike the image fingerprint it covers ancillary chunks too.
+    pub fn file_checksum(&self) -> u32 {
+        CRC_ALGORITHM.checksum(&self.as_bytes())
+    }
+
+    /// Returns the bytes found after the IEND chunk while parsing, which are written
+    /// back unchanged by `as_bytes`.
+    pub fn trailing_bytes(&self) -> &[u8] {
+        &self.trailer
+    }
+
+    /// Removes and returns the bytes found after the IEND chunk
+    pub fn strip_trailing_bytes(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.trailer)
+    }
+
+    /// Returns true if this `Png` was read from a gzip-compressed file
+    pub fn is_gzipped(&self) -> bool {
+        self.gzipped
+    }
+
+    /// Returns the bytes to write back to disk: the same as `as_bytes`, gzip-compressed
+    /// again if this `Png` was read from a gzip-compressed file.
+    pub fn to_file_bytes(&self) -> Result<Vec<u8>> {
+        if !self.gzipped {
+            return Ok(self.as_bytes());
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&self.as_bytes())?;
+        Ok(encoder.finish()?)
+    }
+
+    /// Returns true if `chunk_type` is one of the `STANDARD_CHUNK_TYPES`
+    pub fn is_standard_type(chunk_type: &str) -> bool {
+        Self::STANDARD_CHUNK_TYPES.contains(&chunk_type)
+    }
+
+    /// Counts the custom ancillary chunks, neither critical nor standard, which is
+    /// where messages are hidden, and the bytes of data they hold
+    pub fn total_capacity(&self) -> Capacity {
+        let custom: Vec<&Chunk> = self
+            .iter()
+            .filter(|chunk| {
+                !chunk.chunk_type().is_critical()
+                    && !Self::is_standard_type(&chunk.chunk_type().to_string())
+            })
+            .collect();
+        Capacity {
+            chunks: custom.len(),
+            bytes: custom.iter().map(|chunk| chunk.data().len()).sum(),
+        }
     }
 
-    /// Returns a vector of possible chunks that could have a message
-    pub fn find_possible_messages(&self) -> Option<Vec<&Chunk>> {
-        let candidates: Vec<&Chunk> = self
+    /// Returns a vector of possible chunks that could have a message, most likely first.
+    /// Standard and empty chunks are skipped since they carry image data or metadata.
+    /// Chunks with valid UTF-8 data and a private type, like those pngme writes, rank
+    /// the highest.
+    pub fn find_possible_messages(&self) -> Option<Vec<PossibleMessage<'_>>> {
+        let mut candidates: Vec<PossibleMessage> = self
             .chunks
             .iter()
-            .filter(|&chunk| chunk.data_as_string().unwrap_or_else(|_| "".to_owned()) != "")
+            .filter_map(PossibleMessage::new)
             .collect();
+        candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.rank()));
 
         if !candidates.is_empty() {
             Some(candidates)
@@ -96,93 +742,489 @@ impl Png {
             None
         }
     }
-}
 
-impl TryFrom<&[u8]> for Png {
-    type Error = Error;
+    /// Checks the chunks against the ordering rules of the PNG spec: IHDR first, PLTE,
+    /// tRNS and bKGD before the first IDAT, IDATs contiguous and IEND last.
+    /// Fails with the first rule that is broken.
+    pub fn check_order(&self) -> Result<()> {
+        let types: Vec<String> = self
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        let types: Vec<&str> = types.iter().map(String::as_str).collect();
+        match ORDER_RULES.iter().find(|(_, rule)| !rule(&types)) {
+            Some((description, _)) => Err(PngError::OrderViolation(description).into()),
+            None => Ok(()),
+        }
+    }
 
-    fn try_from(bytes: &[u8]) -> Result<Png> {
-        if bytes.len() < Png::HEADER_LENGHT {
-            return Err(Box::new(PngError::TooSmall));
+    /// Parses the image header stored in the first IHDR chunk
+    pub fn image_header(&self) -> Result<ImageHeader> {
+        let chunk = self
+            .chunk_by_type("IHDR")
+            .ok_or(PngError::MissingImageHeader)?;
+        ImageHeader::try_from(chunk.data())
+    }
+
+    /// Parses the palette histogram stored in the hIST chunk, one frequency per PLTE
+    /// entry. Returns `None` when there is no hIST chunk.
+    pub fn palette_histogram(&self) -> Result<Option<Vec<u16>>> {
+        let Some(hist) = self.chunk_by_type("hIST") else {
+            return Ok(None);
+        };
+        let palette_size = self
+            .chunk_by_type("PLTE")
+            .map_or(0, |plte| plte.data().len() / 3);
+        if hist.data().len() != 2 * palette_size {
+            return Err(PngError::InvalidHistogram(hist.data().len(), palette_size).into());
         }
 
-        let mut chunks: Vec<Chunk> = Vec::new();
-        let mut iter = bytes.iter().copied();
+        let frequencies = hist
+            .data()
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        Ok(Some(frequencies))
+    }
+}
 
-        let header: [u8; Self::HEADER_LENGHT] = iter
-            .by_ref()
-            .take(Self::HEADER_LENGHT)
-            .collect::<Vec<u8>>()
-            .try_into()
-            .unwrap();
+/// A chunk that could hold a message, see `Png::find_possible_messages`
+#[derive(Debug, Clone, Copy)]
+pub struct PossibleMessage<'a> {
+    pub chunk: &'a Chunk,
+    /// The data is valid UTF-8
+    pub utf8: bool,
+    /// The chunk type is private, which pngme uses for the chunks it writes
+    pub private: bool,
+}
 
-        if Self::STANDARD_HEADER != header {
-            return Err(Box::new(PngError::InvalidHeader));
+impl<'a> PossibleMessage<'a> {
+    /// Returns the chunk as a candidate, or `None` when it's standard or empty
+    pub fn new(chunk: &'a Chunk) -> Option<Self> {
+        if Png::is_standard_type(&chunk.chunk_type().to_string()) || chunk.data().is_empty() {
+            return None;
         }
+        Some(PossibleMessage {
+            chunk,
+            utf8: std::str::from_utf8(chunk.data()).is_ok(),
+            private: !chunk.chunk_type().is_public(),
+        })
+    }
 
-        while iter.len() >= Chunk::MIN_BYTES {
-            let size: [u8; Chunk::LENGTH_BYTES] = iter
-                .clone()
-                .take(Chunk::LENGTH_BYTES)
-                .collect::<Vec<u8>>()
-                .try_into()
-                .unwrap();
-            let size: u32 = u32::from_be_bytes(size);
+    /// Higher when the chunk looks more like a message
+    pub fn rank(&self) -> u8 {
+        2 * self.utf8 as u8 + self.private as u8
+    }
 
-            let chunk: Vec<u8> = iter
-                .by_ref()
-                .take(Chunk::MIN_BYTES + size as usize)
-                .collect();
-            let chunk = Chunk::try_from(chunk.as_slice())?;
-            chunks.push(chunk);
+    /// Why the chunk could hold a message
+    pub fn reason(&self) -> &'static str {
+        match (self.utf8, self.private) {
+            (true, true) => "valid UTF-8, private type",
+            (true, false) => "valid UTF-8",
+            (false, true) => "private type",
+            (false, false) => "non-standard type",
         }
-
-        Ok(Self::from_chunks(chunks))
     }
 }
 
-impl fmt::Display for Png {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "PNG File [")?;
-        for chunk in self.chunks.iter() {
-            writeln!(f, "\t{}", chunk)?;
+/// Iterator over the chunks read from a stream, see `Png::stream_chunks`
+pub struct ChunkStream<R> {
+    reader: BufReader<R>,
+    chunks_read: usize,
+    /// Bytes read so far, the signature included
+    position: u64,
+    /// Set after IEND or an error, nothing more is read then
+    done: bool,
+}
+
+impl<R: Read> ChunkStream<R> {
+    /// Starts reading at byte `offset` of `reader`, checking the signature when
+    /// it's 0, see `Png::from_reader_at`
+    fn new(reader: R, offset: u64) -> Result<Self> {
+        let mut reader = BufReader::new(reader);
+
+        if offset == 0 {
+            let mut header: Vec<u8> = Vec::with_capacity(Png::HEADER_LENGHT);
+            reader
+                .by_ref()
+                .take(Png::HEADER_LENGHT as u64)
+                .read_to_end(&mut header)?;
+            Png::check_signature(&header)?;
+        } else {
+            let skipped = io::copy(&mut reader.by_ref().take(offset), &mut io::sink())?;
+            if skipped < offset {
+                return Err(PngError::TooSmall.into());
+            }
         }
-        writeln!(f, "]")?;
-        Ok(())
+
+        Ok(ChunkStream {
+            reader,
+            chunks_read: 0,
+            position: offset.max(Png::HEADER_LENGHT as u64),
+            done: false,
+        })
     }
-}
 
-#[derive(Debug)]
-pub enum PngError {
-    InvalidHeader,
-    TooSmall,
-    UnknownChunkType(String),
+    /// Number of bytes read so far, the signature included
+    pub fn position(&self) -> u64 {
+        self.position
+    }
 }
 
-impl std::error::Error for PngError {}
+impl<R: Read> Iterator for ChunkStream<R> {
+    type Item = Result<Chunk>;
 
-impl fmt::Display for PngError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            PngError::InvalidHeader => write!(f, "Invalid header for PNG file"),
-            PngError::TooSmall => write!(f, "The given source is too small to be a valid PNG file"),
-            PngError::UnknownChunkType(chunk_type) => {
-                write!(f, "Unknown Chunk '{}'", chunk_type)
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match Png::read_chunk(&mut self.reader, self.chunks_read, self.position as usize) {
+            Ok(Some(chunk)) => {
+                self.done = chunk.chunk_type().to_string() == "IEND";
+                self.chunks_read += 1;
+                self.position += (Chunk::MIN_BYTES + chunk.data().len()) as u64;
+                Some(Ok(chunk))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
             }
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::chunk::Chunk;
-    use crate::chunk_type::ChunkType;
-    use std::convert::TryFrom;
-    use std::str::FromStr;
+/// The custom ancillary chunks of a PNG, see `Png::total_capacity`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capacity {
+    /// Number of custom ancillary chunks
+    pub chunks: usize,
+    /// Total bytes of data in those chunks
+    pub bytes: usize,
+}
 
-    fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
+impl Capacity {
+    /// The most data a single chunk can hold, the limit of its 4 bytes length field
+    pub const MAX_CHUNK_BYTES: usize = u32::MAX as usize;
+}
+
+/// A chunk whose stored CRC differs from the one computed from its type and data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrcMismatch {
+    /// Position of the chunk in the file
+    pub index: usize,
+    /// The CRC found in the file
+    pub stored: u32,
+    /// The CRC the chunk should have
+    pub computed: u32,
+}
+
+/// The image properties stored in the IHDR chunk
+/// http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.IHDR
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: u8,
+}
+
+impl ImageHeader {
+    /// The size of the IHDR chunk data
+    pub const LENGTH: usize = 13;
+
+    /// Returns the name of the color type as given by the PNG spec
+    pub fn color_type_name(&self) -> &'static str {
+        match self.color_type {
+            0 => "Grayscale",
+            2 => "Truecolor",
+            3 => "Indexed-color",
+            4 => "Grayscale with alpha",
+            6 => "Truecolor with alpha",
+            _ => "Unknown",
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for ImageHeader {
+    type Error = Error;
+
+    fn try_from(data: &[u8]) -> Result<ImageHeader> {
+        if data.len() < Self::LENGTH {
+            return Err(PngError::InvalidImageHeader(data.len()).into());
+        }
+        let u32_at = |start: usize| u32::from_be_bytes(data[start..start + 4].try_into().unwrap());
+
+        Ok(ImageHeader {
+            width: u32_at(0),
+            height: u32_at(4),
+            bit_depth: data[8],
+            color_type: data[9],
+            compression_method: data[10],
+            filter_method: data[11],
+            interlace_method: data[12],
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for Png {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Png> {
+        let png = Self::parse(bytes, None, true)?;
+        png.check_iend()?;
+        png.check_trailer()?;
+        Ok(png)
+    }
+}
+
+impl<'a> IntoIterator for &'a Png {
+    type Item = &'a Chunk;
+    type IntoIter = std::slice::Iter<'a, Chunk>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Png {
+    /// Parses a whole PNG file. CRC mismatches are pushed to `crc_errors` when given,
+    /// the chunks being read with `Chunk::from_bytes_unchecked` and then given a correct
+    /// CRC, otherwise the first one is returned as an error. Chunk types are only
+    /// checked to be made of letters when `validate_types` is set or `crc_errors` given.
+    fn parse(
+        bytes: &[u8],
+        mut crc_errors: Option<&mut Vec<CrcMismatch>>,
+        validate_types: bool,
+    ) -> Result<Png> {
+        Self::check_signature(bytes)?;
+
+        let mut chunks: Vec<Chunk> = Vec::new();
+        let mut iter = bytes[Self::HEADER_LENGHT..].iter().copied();
+
+        while iter.len() > 0 {
+            let offset = bytes.len() - iter.len();
+            if iter.len() < Chunk::MIN_BYTES {
+                return Err(PngError::Truncated(chunks.len(), offset).into());
+            }
+
+            let size: [u8; Chunk::LENGTH_BYTES] = iter
+                .clone()
+                .take(Chunk::LENGTH_BYTES)
+                .collect::<Vec<u8>>()
+                .try_into()
+                .unwrap();
+            let size: u32 = u32::from_be_bytes(size);
+            if iter.len() < Chunk::MIN_BYTES + size as usize {
+                return Err(PngError::Truncated(chunks.len(), offset).into());
+            }
+
+            let chunk: Vec<u8> = iter
+                .by_ref()
+                .take(Chunk::MIN_BYTES + size as usize)
+                .collect();
+            let chunk = match crc_errors.as_deref_mut() {
+                Some(errors) => {
+                    let chunk = Chunk::from_bytes_unchecked(&chunk)?;
+                    if !chunk.verify_crc() {
+                        errors.push(CrcMismatch {
+                            index: chunks.len(),
+                            stored: chunk.crc(),
+                            computed: chunk.compute_crc(),
+                        });
+                    }
+                    Chunk::from_parts(chunk.into_parts())
+                }
+                None => {
+                    let (chunk, stored) = Chunk::parse(&chunk, validate_types)?;
+                    if stored != chunk.crc() {
+                        return Err(ChunkError::InvalidCrc(chunk.crc(), stored).into());
+                    }
+                    chunk
+                }
+            };
+            let is_end = chunk.chunk_type().to_string() == "IEND";
+            chunks.push(chunk);
+            if is_end {
+                break;
+            }
+        }
+
+        let mut png = Self::from_chunks(chunks);
+        png.trailer = iter.collect();
+        Ok(png)
+    }
+
+    /// Returns true if the last chunk is IEND
+    fn ends_with_iend(&self) -> bool {
+        self.chunks
+            .last()
+            .is_some_and(|last| last.chunk_type().to_string() == "IEND")
+    }
+
+    /// Fails if the last chunk isn't IEND, which every way of reading a PNG requires
+    /// except `from_bytes_lenient` and `from_bytes_salvaged`
+    pub fn check_iend(&self) -> Result<()> {
+        if !self.ends_with_iend() {
+            return Err(PngError::MissingIend.into());
+        }
+        Ok(())
+    }
+
+    /// Fails if the data after IEND holds anything but zero padding. Only `from_bytes`
+    /// enforces this, the other ways of reading a PNG keep the data, see `trailing_bytes`.
+    pub fn check_trailer(&self) -> Result<()> {
+        if self.trailer.starts_with(&Self::STANDARD_HEADER) {
+            return Err(PngError::Concatenated.into());
+        }
+        if self.trailer.iter().any(|&byte| byte != 0) {
+            return Err(PngError::TrailingData(self.trailer.len()).into());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Png {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "PNG File [")?;
+        for chunk in self {
+            writeln!(f, "\t{}", chunk)?;
+        }
+        writeln!(f, "]")?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum PngError {
+    InvalidSignature(Vec<u8>),
+    TooSmall,
+    UnknownChunkType(String),
+    FileNotFound(PathBuf),
+    BrokenSymlink(PathBuf),
+    IndexOutOfRange(usize, usize),
+    InvalidNamespace(String),
+    NamespaceCollision(String, String),
+    ImmovableChunk(String),
+    Truncated(usize, usize),
+    MissingImageHeader,
+    MissingIend,
+    InvalidImageHeader(usize),
+    InvalidHistogram(usize, usize),
+    TrailingData(usize),
+    Concatenated,
+    MisplacedChunk(String),
+    OrderViolation(&'static str),
+}
+
+impl std::error::Error for PngError {}
+
+impl fmt::Display for PngError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PngError::InvalidSignature(found) => {
+                let hex = |bytes: &[u8]| -> String {
+                    bytes
+                        .iter()
+                        .map(|byte| format!("{:02x}", byte))
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                };
+                write!(
+                    f,
+                    "Invalid PNG signature: expected {} but found {}",
+                    hex(&Png::STANDARD_HEADER),
+                    hex(found)
+                )
+            }
+            PngError::TooSmall => write!(f, "The given source is too small to be a valid PNG file"),
+            PngError::UnknownChunkType(chunk_type) => {
+                write!(f, "Unknown Chunk '{}'", chunk_type)
+            }
+            PngError::FileNotFound(path) => write!(f, "File not found: {}", path.display()),
+            PngError::BrokenSymlink(path) => write!(
+                f,
+                "Broken symlink: {} points to a file that doesn't exist",
+                path.display()
+            ),
+            PngError::IndexOutOfRange(index, len) => write!(
+                f,
+                "Chunk index {} is out of range for a PNG with {} chunks",
+                index, len
+            ),
+            PngError::InvalidNamespace(prefix) => write!(
+                f,
+                "Invalid namespace '{}', expected two ASCII letters",
+                prefix
+            ),
+            PngError::NamespaceCollision(old, new) => write!(
+                f,
+                "Renaming Chunk '{}' to '{}' would clash with a standard or existing Chunk type",
+                old, new
+            ),
+            PngError::Truncated(chunks, offset) => write!(
+                f,
+                "The file is truncated: it ends inside the chunk starting at byte {} \
+                 after {} complete chunk(s), which are still intact. \
+                 Use `pngme repair` to keep them and add back IEND",
+                offset, chunks
+            ),
+            PngError::ImmovableChunk(chunk_type) => {
+                write!(f, "Chunk '{}' can't be moved from its position", chunk_type)
+            }
+            PngError::MissingImageHeader => write!(f, "The file has no IHDR Chunk"),
+            PngError::MissingIend => write!(
+                f,
+                "The file has no IEND Chunk, use `pngme repair` to add it back"
+            ),
+            PngError::InvalidImageHeader(len) => write!(
+                f,
+                "The IHDR Chunk holds {} bytes, expected {}",
+                len,
+                ImageHeader::LENGTH
+            ),
+            PngError::TrailingData(len) => write!(
+                f,
+                "Found {} byte(s) of data after the IEND Chunk, see the `trailer` command",
+                len
+            ),
+            PngError::MisplacedChunk(chunk_type) => write!(
+                f,
+                "Chunk '{}' is missing or not where the PNG spec requires it",
+                chunk_type
+            ),
+            PngError::OrderViolation(rule) => write!(f, "Chunk order violation: {}", rule),
+            PngError::Concatenated => write!(
+                f,
+                "Found another PNG file after the IEND Chunk, see the `split` command"
+            ),
+            PngError::InvalidHistogram(len, palette_size) => write!(
+                f,
+                "The hIST Chunk holds {} bytes, expected 2 per each of the {} PLTE entries",
+                len, palette_size
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::PngMeError;
+    use std::convert::TryFrom;
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    #[allow(clippy::vec_init_then_push)]
+    fn testing_chunks() -> Vec<Chunk> {
+        let mut chunks = Vec::new();
 
         chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
         chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
@@ -197,10 +1239,7 @@ mod tests {
     }
 
     fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk> {
-        let chunk_type = ChunkType::from_str(chunk_type)?;
-        let data: Vec<u8> = data.bytes().collect();
-
-        Ok(Chunk::new(chunk_type, data))
+        Chunk::from_type_and_str(chunk_type, data)
     }
 
     #[test]
@@ -213,7 +1252,9 @@ mod tests {
 
     #[test]
     fn test_valid_from_bytes() {
-        let chunk_bytes: Vec<u8> = testing_chunks()
+        let mut chunks = testing_chunks();
+        chunks.push(chunk_from_strings("IEND", "").unwrap());
+        let chunk_bytes: Vec<u8> = chunks
             .into_iter()
             .flat_map(|chunk| chunk.as_bytes())
             .collect();
@@ -247,6 +1288,28 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_valid_signature() {
+        assert!(Png::check_signature(&PNG_FILE[..Png::HEADER_LENGHT]).is_ok());
+    }
+
+    #[test]
+    fn test_truncated_signature() {
+        let err = Png::try_from(&PNG_FILE[..5]).unwrap_err();
+        assert!(matches!(err, PngMeError::Png(PngError::TooSmall)));
+    }
+
+    #[test]
+    fn test_jpeg_signature() {
+        let jpeg: [u8; 12] = [255, 216, 255, 224, 0, 16, 74, 70, 73, 70, 0, 1];
+        let err = Png::try_from(&jpeg[..]).unwrap_err();
+        assert!(matches!(
+            err,
+            PngMeError::Png(PngError::InvalidSignature(_))
+        ));
+        assert!(err.to_string().ends_with("found ff d8 ff e0 00 10 4a 46"));
+    }
+
     #[test]
     fn test_invalid_chunk() {
         let mut chunk_bytes: Vec<u8> = testing_chunks()
@@ -269,6 +1332,36 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_truncated_file() {
+        let cut = PNG_FILE.len() - 20;
+        let err = Png::try_from(&PNG_FILE[..cut]).unwrap_err();
+
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let complete = png.chunks().len() - 2;
+        let offset = PNG_FILE.len()
+            - png.chunks()[complete..]
+                .iter()
+                .map(|chunk| chunk.as_bytes().len())
+                .sum::<usize>();
+        match &err {
+            PngMeError::Png(PngError::Truncated(chunks, at)) => {
+                assert_eq!(*chunks, complete);
+                assert_eq!(*at, offset);
+            }
+            _ => panic!("expected a truncation error, got {}", err),
+        }
+        assert!(err.to_string().contains(&format!("byte {}", offset)));
+        assert!(err.to_string().contains("pngme repair"));
+
+        let (salvaged, crc_errors, dropped) = Png::from_bytes_salvaged(&PNG_FILE[..cut]).unwrap();
+        assert!(crc_errors.is_empty());
+        assert_eq!(dropped, cut - offset);
+        assert_eq!(salvaged.chunks().len(), complete + 1);
+        assert_eq!(&salvaged.as_bytes()[..offset], &PNG_FILE[..offset]);
+        assert_eq!(salvaged.chunks()[complete].chunk_type().to_string(), "IEND");
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -276,6 +1369,71 @@ mod tests {
         assert_eq!(chunks.len(), 3);
     }
 
+    #[test]
+    fn test_iterate_chunks() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut count = 0;
+        for _chunk in &png {
+            count += 1;
+        }
+        assert_eq!(count, png.chunks().len());
+        assert_eq!(png.iter().last().unwrap().chunk_type().to_string(), "IEND");
+    }
+
+    #[test]
+    fn test_stream_possible_messages() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(Chunk::from_type_and_str("tEXt", "Comment\0standard").unwrap());
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("abCd").unwrap(),
+            vec![0xff, 0],
+        ));
+        png.append_chunk(Chunk::from_type_and_str("ABcd", "public").unwrap());
+        png.append_chunk(Chunk::from_type_and_str("ruSt", "planted").unwrap());
+
+        let streamed: Vec<Chunk> = Png::stream_possible_messages(io::Cursor::new(png.as_bytes()))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        let mut streamed: Vec<PossibleMessage> =
+            streamed.iter().filter_map(PossibleMessage::new).collect();
+        streamed.sort_by_key(|candidate| std::cmp::Reverse(candidate.rank()));
+
+        let in_memory = png.find_possible_messages().unwrap();
+        assert_eq!(streamed.len(), in_memory.len());
+        for (streamed, in_memory) in streamed.iter().zip(&in_memory) {
+            assert_eq!(streamed.chunk.as_bytes(), in_memory.chunk.as_bytes());
+            assert_eq!(streamed.reason(), in_memory.reason());
+        }
+    }
+
+    #[test]
+    fn test_find_possible_messages() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(Chunk::from_type_and_str("tEXt", "Comment\0standard").unwrap());
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("abCd").unwrap(),
+            vec![0xff, 0],
+        ));
+        png.append_chunk(Chunk::from_type_and_str("ABcd", "public").unwrap());
+        png.append_chunk(Chunk::from_type_and_str("ruSt", "planted").unwrap());
+
+        let found = png.find_possible_messages().unwrap();
+        let found: Vec<(String, &str)> = found
+            .iter()
+            .map(|m| (m.chunk.chunk_type().to_string(), m.reason()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("RuSt".to_string(), "valid UTF-8, private type"),
+                ("ruSt".to_string(), "valid UTF-8, private type"),
+                ("ABcd".to_string(), "valid UTF-8"),
+                ("abCd".to_string(), "private type"),
+            ]
+        );
+    }
+
     #[test]
     fn test_chunk_by_type() {
         let png = testing_png();
@@ -284,6 +1442,22 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "I am the first chunk");
     }
 
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        for message in ["one", "two", "three"] {
+            png.append_chunk(chunk_from_strings("ruSt", message).unwrap());
+        }
+
+        let messages: Vec<String> = png
+            .chunks_by_type("ruSt")
+            .iter()
+            .map(|chunk| chunk.data_as_string().unwrap())
+            .collect();
+        assert_eq!(messages, ["one", "two", "three"]);
+        assert!(png.chunks_by_type("TeSt").is_empty());
+    }
+
     #[test]
     fn test_append_chunk() {
         let mut png = testing_png();
@@ -293,6 +1467,86 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    #[test]
+    fn test_retain_critical() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let critical = png
+            .chunks()
+            .iter()
+            .filter(|chunk| chunk.chunk_type().is_critical())
+            .count();
+        assert!(critical < png.chunks().len());
+
+        png.retain(|chunk| chunk.chunk_type().is_critical());
+        assert_eq!(png.chunks().len(), critical);
+        assert!(png.chunks().iter().all(|c| c.chunk_type().is_critical()));
+
+        png.retain(|_| false);
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "IEND"]);
+    }
+
+    #[test]
+    fn test_check_order() {
+        let png_with = |types: &[&str]| {
+            Png::from_chunks(
+                types
+                    .iter()
+                    .map(|chunk_type| chunk_from_strings(chunk_type, "").unwrap())
+                    .collect(),
+            )
+        };
+
+        let png = png_with(&["IHDR", "PLTE", "tRNS", "IDAT", "IDAT", "tEXt", "IEND"]);
+        assert!(png.check_order().is_ok());
+
+        let png = png_with(&["IHDR", "IDAT", "PLTE", "IEND"]);
+        let err = png.check_order().unwrap_err();
+        assert!(matches!(
+            err,
+            PngMeError::Png(PngError::OrderViolation(rule)) if rule.starts_with("PLTE")
+        ));
+
+        let png = png_with(&["IHDR", "IDAT", "tEXt", "IDAT", "IEND"]);
+        assert!(png
+            .check_order()
+            .unwrap_err()
+            .to_string()
+            .contains("contiguous"));
+    }
+
+    #[test]
+    fn test_set_chunks() {
+        let mut png = testing_png();
+        let chunks = vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("ruSt", "message").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ];
+        png.set_chunks(chunks).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(png.chunks()[1].data(), b"message");
+    }
+
+    #[test]
+    fn test_set_chunks_missing_iend() {
+        let mut png = testing_png();
+        let chunks = vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("ruSt", "message").unwrap(),
+        ];
+        let err = png.set_chunks(chunks).unwrap_err();
+        assert!(matches!(
+            err,
+            PngMeError::Png(PngError::MisplacedChunk(chunk_type)) if chunk_type == "IEND"
+        ));
+        assert_eq!(png.as_bytes(), testing_png().as_bytes());
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
@@ -302,6 +1556,291 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_is_standard_type() {
+        assert!(Png::is_standard_type("IDAT"));
+        assert!(!Png::is_standard_type("ruSt"));
+    }
+
+    #[test]
+    fn test_dedup_keeps_first() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am another chunk").unwrap());
+        let removed = png.dedup(false);
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(removed, 1);
+        assert_eq!(types, ["FrSt", "miDl", "LASt"]);
+    }
+
+    #[test]
+    fn test_dedup_keeps_last() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am another chunk").unwrap());
+        let removed = png.dedup(true);
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(removed, 1);
+        assert_eq!(types, ["FrSt", "LASt", "miDl"]);
+    }
+
+    #[test]
+    fn test_from_file_not_found() {
+        let path = std::env::temp_dir().join("pngme_test_missing.png");
+        let err = Png::from_file(&path).unwrap_err();
+        assert!(err.to_string().starts_with("File not found"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_from_file_broken_symlink() {
+        let dir = std::env::temp_dir();
+        let link = dir.join("pngme_test_dangling.png");
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink(dir.join("pngme_test_nowhere.png"), &link).unwrap();
+
+        let err = Png::from_file(&link).unwrap_err();
+        fs::remove_file(&link).unwrap();
+        assert!(err.to_string().starts_with("Broken symlink"));
+    }
+
+    #[test]
+    fn test_from_reader_cursor() {
+        let png = Png::from_reader(Cursor::new(PNG_FILE.to_vec())).unwrap();
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+
+        let truncated = PNG_FILE[..PNG_FILE.len() - 5].to_vec();
+        let err = Png::from_reader(Cursor::new(truncated)).unwrap_err();
+        assert!(matches!(err, PngMeError::Png(PngError::Truncated(_, _))));
+    }
+
+    #[test]
+    fn test_from_bytes_embedded() {
+        let mut container: Vec<u8> = vec![0xaa; 100];
+        container.extend_from_slice(&PNG_FILE);
+        container.extend_from_slice(b"rest of the container");
+
+        let png = Png::from_bytes_embedded(&container, 100).unwrap();
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+        assert!(Png::from_bytes_embedded(&container, 99).is_err());
+        assert!(Png::from_bytes_embedded(&container, container.len() + 1).is_err());
+    }
+
+    #[test]
+    fn test_from_reader_with_progress() {
+        let mut reported: Vec<u64> = Vec::new();
+        let png = Png::from_reader_with_progress(Cursor::new(PNG_FILE.to_vec()), |read| {
+            reported.push(read)
+        })
+        .unwrap();
+
+        assert_eq!(reported.len(), png.chunks().len());
+        assert_eq!(reported.last(), Some(&(PNG_FILE.len() as u64)));
+        assert!(reported.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_from_reader_at_chunk_offset() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let offset: usize = Png::HEADER_LENGHT
+            + png.chunks()[..2]
+                .iter()
+                .map(|chunk| chunk.as_bytes().len())
+                .sum::<usize>();
+
+        let resumed =
+            Png::from_reader_at(std::io::Cursor::new(&PNG_FILE[..]), offset as u64).unwrap();
+        let expected: Vec<Vec<u8>> = png.chunks()[2..].iter().map(|c| c.as_bytes()).collect();
+        let actual: Vec<Vec<u8>> = resumed.chunks().iter().map(|c| c.as_bytes()).collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_insert_chunk() {
+        let types = |png: &Png| -> Vec<String> {
+            png.iter()
+                .map(|chunk| chunk.chunk_type().to_string())
+                .collect()
+        };
+        let mut png = testing_png();
+
+        png.insert_chunk(0, chunk_from_strings("ruSt", "front").unwrap())
+            .unwrap();
+        assert_eq!(types(&png), ["ruSt", "FrSt", "miDl", "LASt"]);
+
+        png.insert_chunk(2, chunk_from_strings("abCd", "middle").unwrap())
+            .unwrap();
+        assert_eq!(types(&png), ["ruSt", "FrSt", "abCd", "miDl", "LASt"]);
+
+        let err = png
+            .insert_chunk(6, chunk_from_strings("ruSt", "invalid").unwrap())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            PngMeError::Png(PngError::IndexOutOfRange(6, 5))
+        ));
+        assert_eq!(png.chunks().len(), 5);
+
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        let err = png
+            .insert_chunk(0, chunk_from_strings("ruSt", "front").unwrap())
+            .unwrap_err();
+        assert!(matches!(err, PngMeError::Png(PngError::ImmovableChunk(ref t)) if t == "IHDR"));
+        let err = png
+            .insert_chunk(2, chunk_from_strings("ruSt", "end").unwrap())
+            .unwrap_err();
+        assert!(matches!(err, PngMeError::Png(PngError::ImmovableChunk(ref t)) if t == "IEND"));
+        png.insert_chunk(1, chunk_from_strings("ruSt", "inside").unwrap())
+            .unwrap();
+        assert_eq!(types(&png), ["IHDR", "ruSt", "IEND"]);
+    }
+
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = testing_png();
+        let new = chunk_from_strings("ruSt", "Replacement").unwrap();
+        let old = png.replace_chunk(1, new).unwrap();
+
+        assert_eq!(old.chunk_type().to_string(), "miDl");
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["FrSt", "ruSt", "LASt"]);
+        assert_eq!(png.chunks()[1].data_as_string().unwrap(), "Replacement");
+
+        let new = chunk_from_strings("ruSt", "Out of range").unwrap();
+        assert!(png.replace_chunk(3, new).is_err());
+    }
+
+    #[test]
+    fn test_replace_chunk_data() {
+        let mut png = testing_png();
+        png.replace_chunk_data("miDl", b"New middle".to_vec())
+            .unwrap();
+
+        let chunk = &png.chunks()[1];
+        assert_eq!(chunk.chunk_type().to_string(), "miDl");
+        assert_eq!(chunk.data_as_string().unwrap(), "New middle");
+        assert_eq!(chunk.crc(), chunk.compute_crc());
+        assert_eq!(png.chunks().len(), 3);
+
+        assert!(png.replace_chunk_data("ruSt", Vec::new()).is_err());
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_swap_chunks() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("ruSt", "Message").unwrap());
+        png.swap_chunks(1, 3).unwrap();
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["FrSt", "ruSt", "LASt", "miDl"]);
+    }
+
+    #[test]
+    fn test_swap_chunks_rejects_iend() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("IEND", "").unwrap());
+
+        assert!(png.swap_chunks(1, 3).is_err());
+        assert!(png.swap_chunks(1, 4).is_err());
+    }
+
+    #[test]
+    fn test_apply_namespace() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("tEXt", "Comment\0standard").unwrap());
+        png.append_chunk(chunk_from_strings("ruSt", "custom").unwrap());
+        png.append_chunk(chunk_from_strings("aBCd", "custom").unwrap());
+
+        let renamed = png.apply_namespace("PM").unwrap();
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(renamed, 3);
+        assert_eq!(types, ["FrSt", "pmDl", "LASt", "tEXt", "pmSt", "pMCd"]);
+        assert!(png
+            .chunks()
+            .iter()
+            .all(|chunk| chunk.chunk_type().is_valid()));
+    }
+
+    #[test]
+    fn test_apply_namespace_collision() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("aBXt", "custom").unwrap());
+        let err = png.apply_namespace("te").unwrap_err();
+        assert!(matches!(
+            err,
+            PngMeError::Png(PngError::NamespaceCollision(ref old, ref new))
+                if old == "aBXt" && new == "tEXt"
+        ));
+
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("ruSt", "one").unwrap());
+        png.append_chunk(chunk_from_strings("pmSt", "two").unwrap());
+        assert!(png.apply_namespace("pm").is_err());
+
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("ruSt", "one").unwrap());
+        png.append_chunk(chunk_from_strings("xySt", "two").unwrap());
+        assert!(png.apply_namespace("pm").is_err());
+        // Nothing was renamed
+        assert!(png.chunk_by_type("ruSt").is_some());
+        assert!(png.chunk_by_type("miDl").is_some());
+    }
+
+    #[test]
+    fn test_remove_one_of_many() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "one").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "two").unwrap());
+
+        let removed = png.remove_chunk("TeSt").unwrap();
+        assert_eq!(&removed.data_as_string().unwrap(), "one");
+        assert_eq!(png.chunks_by_type("TeSt").len(), 1);
+    }
+
+    #[test]
+    fn test_remove_all_chunks() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "one").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "two").unwrap());
+
+        assert_eq!(png.remove_all_chunks("TeSt").len(), 2);
+        assert!(png.chunk_by_type("TeSt").is_none());
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_remove_all_chunks_none_present() {
+        let mut png = testing_png();
+        assert!(png.remove_all_chunks("TeSt").is_empty());
+        assert!(png.remove_chunk("TeSt").is_err());
+        assert_eq!(png.chunks().len(), 3);
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -316,9 +1855,218 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_image_header() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let header = png.image_header().unwrap();
+        assert_eq!((header.width, header.height), (50, 50));
+        assert_eq!(header.bit_depth, 8);
+        assert_eq!(header.color_type_name(), "Truecolor with alpha");
+        assert_eq!(header.interlace_method, 0);
+    }
+
+    #[test]
+    fn test_image_header_missing_or_short() {
+        let err = testing_png().image_header().unwrap_err();
+        assert!(matches!(err, PngMeError::Png(PngError::MissingImageHeader)));
+
+        let png = Png::from_chunks(vec![chunk_from_strings("IHDR", "short").unwrap()]);
+        let err = png.image_header().unwrap_err();
+        assert!(matches!(
+            err,
+            PngMeError::Png(PngError::InvalidImageHeader(5))
+        ));
+    }
+
+    #[test]
+    fn test_palette_histogram() {
+        let mut png = testing_png();
+        assert!(png.palette_histogram().unwrap().is_none());
+
+        let palette: Vec<u8> = vec![255, 0, 0, 0, 0, 255];
+        png.append_chunk(Chunk::new(ChunkType::from_str("PLTE").unwrap(), palette));
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("hIST").unwrap(),
+            vec![0, 7, 1, 0],
+        ));
+        assert_eq!(png.palette_histogram().unwrap(), Some(vec![7, 256]));
+
+        png.extend_chunk_data(png.chunks().len() - 1, &[0, 1])
+            .unwrap();
+        let err = png.palette_histogram().unwrap_err();
+        assert!(matches!(
+            err,
+            PngMeError::Png(PngError::InvalidHistogram(6, 2))
+        ));
+    }
+
+    #[test]
+    fn test_from_bytes_lenient_collects_crc_errors() {
+        let png = testing_png();
+        let mut bytes = png.as_bytes();
+        let mut end = Png::HEADER_LENGHT;
+        for (index, chunk) in png.chunks().iter().enumerate() {
+            end += chunk.as_bytes().len();
+            if index != 1 {
+                bytes[end - 1] ^= 0xff;
+            }
+        }
+        assert!(Png::try_from(bytes.as_slice()).is_err());
+
+        let (fixed, errors) = Png::from_bytes_lenient(&bytes).unwrap();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].index, 0);
+        assert_eq!(errors[0].computed, png.chunks()[0].crc());
+        assert_eq!(errors[0].stored, png.chunks()[0].crc() ^ 0xff);
+        assert_eq!(errors[1].index, 2);
+        assert_eq!(fixed.as_bytes(), png.as_bytes());
+        assert_eq!(Png::collect_crc_errors(&bytes).unwrap(), errors);
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let mut bytes: Vec<u8> = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"appended data");
+
+        let png = Png::from_bytes_with_trailer(&bytes).unwrap();
+        assert_eq!(png.trailing_bytes(), b"appended data");
+        assert_eq!(
+            png.chunks().last().unwrap().chunk_type().to_string(),
+            "IEND"
+        );
+        assert_eq!(png.as_bytes(), bytes);
+
+        let png = Png::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(png.trailing_bytes(), b"appended data");
+        assert_eq!(png.as_bytes(), bytes);
+        let err = png.check_trailer().unwrap_err();
+        assert!(matches!(err, PngMeError::Png(PngError::TrailingData(13))));
+    }
+
+    #[test]
+    fn test_try_from_rejects_trailing_data() {
+        assert!(Png::try_from(&PNG_FILE[..]).is_ok());
+
+        let mut bytes: Vec<u8> = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"appended data");
+        let err = Png::try_from(bytes.as_slice()).unwrap_err();
+        assert!(matches!(err, PngMeError::Png(PngError::TrailingData(13))));
+
+        let mut padded: Vec<u8> = PNG_FILE.to_vec();
+        padded.extend_from_slice(&[0; 4]);
+        assert_eq!(Png::try_from(padded.as_slice()).unwrap().as_bytes(), padded);
+    }
+
+    #[test]
+    fn test_concatenated_pngs() {
+        let mut bytes: Vec<u8> = PNG_FILE.to_vec();
+        bytes.extend_from_slice(&PNG_FILE);
+
+        let err = Png::try_from(bytes.as_slice()).unwrap_err();
+        assert!(matches!(err, PngMeError::Png(PngError::Concatenated)));
+
+        let pngs = Png::from_bytes_multi(&bytes).unwrap();
+        assert_eq!(pngs.len(), 2);
+        for png in pngs {
+            assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+        }
+        assert_eq!(Png::from_bytes_multi(&PNG_FILE).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_from_bytes_raw_types() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("IEND", "").unwrap());
+        png.append_chunk(Chunk::new(
+            ChunkType::from_bytes_unchecked(*b"Ru5t"),
+            b"digit".to_vec(),
+        ));
+        let bytes = png.as_bytes();
+
+        assert!(Png::try_from(bytes.as_slice()).is_err());
+        let png = Png::from_bytes_raw_types(&bytes).unwrap();
+        assert_eq!(png.chunk_by_type("Ru5t").unwrap().data(), b"digit");
+    }
+
+    #[test]
+    fn test_try_from_without_iend() {
+        let bytes = testing_png().as_bytes();
+        let err = Png::try_from(bytes.as_slice()).unwrap_err();
+        assert!(matches!(err, PngMeError::Png(PngError::MissingIend)));
+
+        for err in [
+            Png::from_reader(bytes.as_slice()).unwrap_err(),
+            Png::from_bytes_with_trailer(&bytes).unwrap_err(),
+            Png::from_bytes_raw_types(&bytes).unwrap_err(),
+            Png::from_bytes_embedded(&bytes, 0).unwrap_err(),
+            Png::from_bytes_multi(&bytes).unwrap_err(),
+        ] {
+            assert!(matches!(err, PngMeError::Png(PngError::MissingIend)));
+        }
+        let (png, _) = Png::from_bytes_lenient(&bytes).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_total_capacity() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("tEXt", "Comment\0standard").unwrap());
+        png.append_chunk(chunk_from_strings("IDAT", "critical").unwrap());
+        for message in ["one", "two", "three"] {
+            png.append_chunk(chunk_from_strings("ruSt", message).unwrap());
+        }
+
+        // miDl is the only custom ancillary chunk of the testing PNG
+        let capacity = png.total_capacity();
+        assert_eq!(capacity.chunks, 1 + 3);
+        assert_eq!(capacity.bytes, "I am another chunk".len() + 3 + 3 + 5);
+    }
+
+    #[test]
+    fn test_share_duplicate_data() {
+        let mut png = testing_png();
+        for _ in 0..100 {
+            png.append_chunk(Chunk::new(
+                ChunkType::from_str("ruSt").unwrap(),
+                vec![42; 1000],
+            ));
+        }
+        let bytes = png.as_bytes();
+        assert!(png.data_bytes_in_memory() >= 100 * 1000);
+
+        assert_eq!(png.share_duplicate_data(), 99);
+        assert!(png.data_bytes_in_memory() < 2 * 1000);
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_file_checksum_changes_with_any_chunk() {
+        let checksum = testing_png().file_checksum();
+        assert_eq!(testing_png().file_checksum(), checksum);
+
+        for index in 0..testing_png().chunks().len() {
+            let mut png = testing_png();
+            png.extend_chunk_data(index, b"!").unwrap();
+            assert_ne!(png.file_checksum(), checksum);
+        }
+    }
+
+    #[test]
+    fn test_strip_trailing_bytes() {
+        let mut bytes: Vec<u8> = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"appended data");
+
+        let mut png = Png::from_bytes_with_trailer(&bytes).unwrap();
+        assert_eq!(png.strip_trailing_bytes(), b"appended data");
+        assert!(png.trailing_bytes().is_empty());
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
     #[test]
     fn test_png_trait_impls() {
-        let chunk_bytes: Vec<u8> = testing_chunks()
+        let mut chunks = testing_chunks();
+        chunks.push(chunk_from_strings("IEND", "").unwrap());
+        let chunk_bytes: Vec<u8> = chunks
             .into_iter()
             .flat_map(|chunk| chunk.as_bytes())
             .collect();
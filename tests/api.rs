@@ -0,0 +1,25 @@
+use std::str::FromStr;
+
+use pngme::chunk::Chunk;
+use pngme::chunk_type::ChunkType;
+use pngme::cipher;
+use pngme::png::Png;
+
+#[test]
+fn test_build_png_with_public_api() {
+    let chunk_type = ChunkType::from_str("ruSt").unwrap();
+    let data = cipher::xor_encode(b"Hidden through the library", "key");
+    let png = Png::from_chunks(vec![
+        Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+        Chunk::new(chunk_type, data),
+        Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+    ]);
+
+    let parsed = Png::from_bytes(&png.as_bytes()).unwrap();
+    let chunk = parsed.chunk_by_type("ruSt").unwrap();
+    assert_eq!(parsed.chunks().len(), 3);
+    assert_eq!(
+        cipher::xor_decode(chunk.data(), "key"),
+        "Hidden through the library"
+    );
+}
@@ -0,0 +1,51 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::str::FromStr;
+
+use pngme::chunk::Chunk;
+use pngme::chunk_type::ChunkType;
+use pngme::png::Png;
+
+/// Writes a PNG with a hidden message and a broken IEND CRC to a temporary file
+fn broken_png(name: &str) -> PathBuf {
+    let chunk = |chunk_type: &str, data: &[u8]| {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    };
+    let png = Png::from_chunks(vec![
+        chunk("IHDR", &[0; 13]),
+        chunk("ruSt", b"hidden"),
+        chunk("IEND", &[]),
+    ]);
+    let mut bytes = png.as_bytes();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+
+    let file = env::temp_dir().join(format!("pngme_cli_{}_{}", std::process::id(), name));
+    fs::write(&file, bytes).unwrap();
+    file
+}
+
+#[test]
+fn test_quiet_prints_nothing() {
+    let file = broken_png("quiet.png");
+    let path = file.to_str().unwrap();
+    let commands: [&[&str]; 4] = [
+        &["repair", path, "--report", "--in-place"],
+        &["capacity", path],
+        &["find", path],
+        &["diff", path, path],
+    ];
+    for command in commands {
+        let output = Command::new(env!("CARGO_BIN_EXE_pngme"))
+            .arg("--quiet")
+            .args(command)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", command);
+        assert!(output.stdout.is_empty(), "{:?}", command);
+        assert!(output.stderr.is_empty(), "{:?}", command);
+    }
+    fs::remove_file(file).unwrap();
+}